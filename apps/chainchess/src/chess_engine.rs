@@ -0,0 +1,1841 @@
+//! Chess logic shared by the contract and the service: move parsing,
+//! legality and SAN, draw rules, evaluation and the AI search, and PGN and
+//! share-code encoding. Everything here works on plain FEN strings and UCI
+//! moves, so it needs no runtime.
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chess::{
+    between, get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_quiets,
+    get_rook_moves, BitBoard, Board, BoardBuilder, BoardStatus, ChessMove, Color, File, MoveGen,
+    Piece, Rank, Square, ALL_SQUARES,
+};
+
+use crate::{
+    ChainChessError, EngineInfo, Personality, PieceKind, PlayerColor, PromotionOption,
+    PromotionOptions, TerminationReason,
+};
+
+/// How a game ended, and why.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchResult {
+    Winner(PlayerColor, TerminationReason),
+    Draw(TerminationReason),
+}
+
+impl MatchResult {
+    pub fn winner(self) -> Option<PlayerColor> {
+        match self {
+            MatchResult::Winner(color, _) => Some(color),
+            MatchResult::Draw(_) => None,
+        }
+    }
+
+    pub fn reason(self) -> TerminationReason {
+        match self {
+            MatchResult::Winner(_, reason) | MatchResult::Draw(reason) => reason,
+        }
+    }
+}
+
+/// Plies the AI searches ahead unless a game sets its own `ai_level`.
+pub const AI_SEARCH_DEPTH: u8 = 3;
+
+/// What this build of the engine supports; keep in step with new features.
+pub fn engine_info() -> EngineInfo {
+    EngineInfo {
+        ai_algorithm: "alpha-beta minimax".to_string(),
+        default_depth: AI_SEARCH_DEPTH,
+        variants: vec!["standard".to_string()],
+        clocks: true,
+        threefold_repetition: true,
+        fifty_move_rule: true,
+        cross_chain: true,
+        features: [
+            "private_lobbies",
+            "share_codes",
+            "training_games",
+            "capture_tracking",
+            "promotion_hints",
+            "rating_decay",
+            "rating_gates",
+            "analysis_forks",
+            "ai_personalities",
+            "ai_levels",
+            "opening_book",
+            "takebacks",
+        ]
+        .map(String::from)
+        .to_vec(),
+    }
+}
+
+/// Outcome of applying a single UCI move to a position.
+pub struct MoveComputation {
+    pub fen: String,
+    pub uci: String,
+    pub san: Option<String>,
+    /// Piece taken by the move, including pawns captured en passant.
+    pub captured: Option<PieceKind>,
+    pub result: Option<MatchResult>,
+}
+
+/// Applies a UCI move (with an optional separate promotion letter) to `current_fen`.
+pub fn apply_uci_move(
+    current_fen: &str,
+    raw_uci: &str,
+    promotion: Option<&str>,
+) -> Result<MoveComputation, ChainChessError> {
+    let fen_board = Board::from_str(current_fen).map_err(|_| illegal_move())?;
+    let promotion = normalize_promotion(promotion)?;
+    let mut uci = raw_uci.trim().to_ascii_lowercase();
+    if uci.len() == 4 {
+        if let Some(promo) = promotion {
+            uci.push(promo);
+        }
+    }
+    let chess_move = king_takes_rook_castle(&fen_board, parse_uci_move(&uci)?);
+    let promotes = fen_board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+        && matches!(chess_move.get_dest().get_rank(), Rank::First | Rank::Eighth);
+    match (promotes, chess_move.get_promotion()) {
+        (true, None) => {
+            return Err(ChainChessError::InvalidMove(
+                "a promotion piece is required".into(),
+            ))
+        }
+        (false, Some(_)) => {
+            return Err(ChainChessError::InvalidMove(
+                "only a pawn reaching the last rank can promote".into(),
+            ))
+        }
+        _ => {}
+    }
+    if !MoveGen::new_legal(&fen_board).any(|legal| legal == chess_move) {
+        return Err(illegal_move());
+    }
+    let board_after = fen_board.make_move_new(chess_move);
+    let status = board_after.status();
+    let result = match status {
+        BoardStatus::Ongoing if is_insufficient_material(&board_after) => {
+            Some(MatchResult::Draw(TerminationReason::InsufficientMaterial))
+        }
+        BoardStatus::Ongoing => None,
+        BoardStatus::Stalemate => Some(MatchResult::Draw(TerminationReason::Stalemate)),
+        BoardStatus::Checkmate => {
+            // In the resulting board it's the opponent's turn but already checkmated.
+            let winner = fen_board.side_to_move();
+            let player_color = if winner == Color::White {
+                PlayerColor::White
+            } else {
+                PlayerColor::Black
+            };
+            Some(MatchResult::Winner(
+                player_color,
+                TerminationReason::Checkmate,
+            ))
+        }
+    };
+    // Generate SAN notation
+    let san = generate_san(&fen_board, chess_move);
+    let captured = captured_piece(&fen_board, chess_move);
+
+    // `Board` does not track the move counters, so carry them over from the input.
+    let (halfmove, fullmove) = move_counters(current_fen);
+    let halfmove =
+        if captured.is_some() || fen_board.piece_on(chess_move.get_source()) == Some(Piece::Pawn) {
+            0
+        } else {
+            halfmove + 1
+        };
+    let fullmove = fullmove + u32::from(fen_board.side_to_move() == Color::Black);
+
+    Ok(MoveComputation {
+        fen: with_move_counters(&board_after.to_string(), halfmove, fullmove),
+        uci: move_to_uci_string(chess_move),
+        san: Some(san),
+        captured: captured.map(PieceKind::from),
+        result,
+    })
+}
+
+/// Halfmove clock and fullmove number of `fen`, defaulting to 0 and 1.
+pub fn move_counters(fen: &str) -> (u32, u32) {
+    let mut fields = fen.split_whitespace().skip(4);
+    let halfmove = fields.next().and_then(|f| f.parse().ok()).unwrap_or(0);
+    let fullmove = fields.next().and_then(|f| f.parse().ok()).unwrap_or(1);
+    (halfmove, fullmove)
+}
+
+/// `fen` with its move counter fields replaced.
+fn with_move_counters(fen: &str, halfmove: u32, fullmove: u32) -> String {
+    let mut position: Vec<String> = fen.split_whitespace().take(4).map(String::from).collect();
+    if let Some(en_passant) = position.get_mut(3) {
+        *en_passant = en_passant_target(en_passant);
+    }
+    format!("{} {halfmove} {fullmove}", position.join(" "))
+}
+
+/// `Board` writes the en passant field as the square of the pawn that just
+/// advanced two squares ("e4"); FEN names the square it passed ("e3").
+fn en_passant_target(field: &str) -> String {
+    match field.as_bytes() {
+        [file, b'4'] => format!("{}3", *file as char),
+        [file, b'5'] => format!("{}6", *file as char),
+        _ => field.to_string(),
+    }
+}
+
+/// Piece captured by `mv`; an en passant capture lands on an empty square.
+pub fn captured_piece(board: &Board, mv: ChessMove) -> Option<Piece> {
+    let source = mv.get_source();
+    let dest = mv.get_dest();
+    board.piece_on(dest).or_else(|| {
+        (board.piece_on(source) == Some(Piece::Pawn) && source.get_file() != dest.get_file())
+            .then_some(Piece::Pawn)
+    })
+}
+
+impl From<Piece> for PieceKind {
+    fn from(piece: Piece) -> Self {
+        match piece {
+            Piece::Pawn => PieceKind::Pawn,
+            Piece::Knight => PieceKind::Knight,
+            Piece::Bishop => PieceKind::Bishop,
+            Piece::Rook => PieceKind::Rook,
+            Piece::Queen => PieceKind::Queen,
+            Piece::King => PieceKind::King,
+        }
+    }
+}
+
+/// Side to move in `fen`, if it parses.
+pub fn side_to_move(fen: &str) -> Option<PlayerColor> {
+    let board = Board::from_str(fen).ok()?;
+    Some(match board.side_to_move() {
+        Color::White => PlayerColor::White,
+        Color::Black => PlayerColor::Black,
+    })
+}
+
+/// All legal moves in `fen`, in UCI notation.
+pub fn legal_moves(fen: &str) -> Vec<String> {
+    match Board::from_str(fen) {
+        Ok(board) => MoveGen::new_legal(&board).map(move_to_uci_string).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether the side to move in `fen` is in check.
+pub fn in_check(fen: &str) -> bool {
+    Board::from_str(fen).is_ok_and(|board| board.checkers().popcnt() > 0)
+}
+
+/// Whether the side to move in `fen` has been checkmated.
+pub fn is_checkmate(fen: &str) -> bool {
+    Board::from_str(fen).is_ok_and(|board| board.status() == BoardStatus::Checkmate)
+}
+
+/// Checks that `fen` describes a reachable position, explaining why not.
+///
+/// Besides what the FEN parser enforces, this names the case where the side
+/// that is not to move stands in check, which no legal game can reach.
+pub fn validate_fen(fen: &str) -> Result<(), ChainChessError> {
+    let invalid = |reason: &str| ChainChessError::InvalidFen(reason.into());
+    let builder = BoardBuilder::from_str(fen).map_err(|_| invalid("FEN could not be parsed"))?;
+    if Board::try_from(&builder).is_ok() {
+        return Ok(());
+    }
+    let mut flipped = builder;
+    flipped
+        .side_to_move(!builder.get_side_to_move())
+        .en_passant(None);
+    match Board::try_from(&flipped) {
+        Ok(board) if board.checkers().popcnt() > 0 => {
+            Err(invalid("the side not to move is in check"))
+        }
+        _ => Err(invalid("position is not legal")),
+    }
+}
+
+/// Whether the side to move in `fen` is stalemated.
+pub fn is_stalemate(fen: &str) -> bool {
+    Board::from_str(fen).is_ok_and(|board| board.status() == BoardStatus::Stalemate)
+}
+
+/// Whether `fen` is a dead draw by material; see [`is_insufficient_material`].
+pub fn insufficient_material(fen: &str) -> bool {
+    Board::from_str(fen).is_ok_and(|board| is_insufficient_material(&board))
+}
+
+/// Light squares of the board (b1, d1, ..., a2, ...).
+const LIGHT_SQUARES: BitBoard = BitBoard(0x55aa_55aa_55aa_55aa);
+
+/// Whether neither side has enough material left to deliver mate: bare kings,
+/// a single knight against a bare king, or only bishops that all stand on
+/// squares of one color (K+B vs K, K+B vs K+B with same-colored bishops).
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy =
+        *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    if heavy.popcnt() > 0 {
+        return false;
+    }
+    let knights = board.pieces(Piece::Knight).popcnt();
+    let bishops = *board.pieces(Piece::Bishop);
+    let light_bishops = (bishops & LIGHT_SQUARES).popcnt();
+    match knights {
+        0 => light_bishops == 0 || light_bishops == bishops.popcnt(),
+        1 => bishops.popcnt() == 0,
+        _ => false,
+    }
+}
+
+/// Whether `color` has pieces that could ever deliver mate in `fen`: a pawn,
+/// rook or queen, two knights, a knight and a bishop, or bishops on both
+/// square colors. The opponent's material is not considered.
+pub fn has_mating_material(fen: &str, color: PlayerColor) -> bool {
+    let Ok(board) = Board::from_str(fen) else {
+        return false;
+    };
+    let own = *board.color_combined(match color {
+        PlayerColor::White => Color::White,
+        PlayerColor::Black => Color::Black,
+    });
+    let heavy =
+        *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    if (heavy & own).popcnt() > 0 {
+        return true;
+    }
+    let knights = (*board.pieces(Piece::Knight) & own).popcnt();
+    let bishops = *board.pieces(Piece::Bishop) & own;
+    let light_bishops = (bishops & LIGHT_SQUARES).popcnt();
+    knights >= 2
+        || (knights == 1 && bishops.popcnt() > 0)
+        || (light_bishops > 0 && light_bishops < bishops.popcnt())
+}
+
+/// Renders `fen` as a Unicode diagram from White's side: ranks 8 to 1, one per
+/// line with its label, followed by a line of file letters.
+pub fn board_unicode(fen: &str) -> Option<String> {
+    let board = Board::from_str(fen).ok()?;
+    let mut lines = Vec::with_capacity(9);
+    for rank in (0..8).rev() {
+        let mut line = format!("{}", rank + 1);
+        for file in 0..8 {
+            let square = Square::make_square(Rank::from_index(rank), File::from_index(file));
+            let glyph = match (board.piece_on(square), board.color_on(square)) {
+                (Some(piece), Some(color)) => piece_glyph(piece, color),
+                _ => '·',
+            };
+            line.push(' ');
+            line.push(glyph);
+        }
+        lines.push(line);
+    }
+    lines.push("  a b c d e f g h".to_string());
+    Some(lines.join("\n"))
+}
+
+fn piece_glyph(piece: Piece, color: Color) -> char {
+    match (color, piece) {
+        (Color::White, Piece::King) => '♔',
+        (Color::White, Piece::Queen) => '♕',
+        (Color::White, Piece::Rook) => '♖',
+        (Color::White, Piece::Bishop) => '♗',
+        (Color::White, Piece::Knight) => '♘',
+        (Color::White, Piece::Pawn) => '♙',
+        (Color::Black, Piece::King) => '♚',
+        (Color::Black, Piece::Queen) => '♛',
+        (Color::Black, Piece::Rook) => '♜',
+        (Color::Black, Piece::Bishop) => '♝',
+        (Color::Black, Piece::Knight) => '♞',
+        (Color::Black, Piece::Pawn) => '♟',
+    }
+}
+
+/// Pieces of `color` attacking `square`, ignoring pins.
+pub fn attackers(board: &Board, square: Square, color: Color) -> BitBoard {
+    let ours = *board.color_combined(color);
+    let occupied = *board.combined();
+    let diagonal = *board.pieces(Piece::Bishop) | *board.pieces(Piece::Queen);
+    let straight = *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    // A pawn of `color` attacks `square` exactly when a pawn of the other
+    // color standing on `square` would attack it.
+    (get_pawn_attacks(square, !color, *board.pieces(Piece::Pawn))
+        | (get_knight_moves(square) & *board.pieces(Piece::Knight))
+        | (get_bishop_moves(square, occupied) & diagonal)
+        | (get_rook_moves(square, occupied) & straight)
+        | (get_king_moves(square) & *board.pieces(Piece::King)))
+        & ours
+}
+
+/// Squares of the side to move's pieces that look en prise.
+///
+/// A piece counts as hanging when it is attacked by more enemy pieces than it
+/// has defenders, or when any attacker is worth less than the piece itself.
+/// This is a static count with no exchange sequence, so at most 64 squares
+/// are inspected and the answer is cheap but only a hint.
+pub fn hanging_pieces(fen: &str) -> Vec<String> {
+    let Ok(board) = Board::from_str(fen) else {
+        return Vec::new();
+    };
+    let us = board.side_to_move();
+    let mut hanging = Vec::new();
+    for square in *board.color_combined(us) {
+        let Some(piece) = board.piece_on(square) else {
+            continue;
+        };
+        if piece == Piece::King {
+            continue;
+        }
+        let enemy = attackers(&board, square, !us);
+        if enemy.popcnt() == 0 {
+            continue;
+        }
+        let defenders = attackers(&board, square, us).popcnt();
+        let cheapest_attacker = enemy
+            .filter_map(|attacker| board.piece_on(attacker))
+            .map(piece_value)
+            .min()
+            .unwrap_or(0);
+        if enemy.popcnt() > defenders || cheapest_attacker < piece_value(piece) {
+            hanging.push(square.to_string());
+        }
+    }
+    hanging
+}
+
+/// Parses UCI syntax (`e2e4`, `e7e8q`) without checking legality; anything
+/// else is a [`ChainChessError::MalformedUci`].
+pub fn parse_uci_move(uci: &str) -> Result<ChessMove, ChainChessError> {
+    let malformed = || ChainChessError::MalformedUci(uci.to_string());
+    if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+        return Err(malformed());
+    }
+    let from = Square::from_str(&uci[0..2]).map_err(|_| malformed())?;
+    let to = Square::from_str(&uci[2..4]).map_err(|_| malformed())?;
+    let promo_piece = match uci[4..].chars().next() {
+        Some(letter) => Some(promotion_piece(letter).ok_or_else(malformed)?),
+        None => None,
+    };
+    Ok(ChessMove::new(from, to, promo_piece))
+}
+
+/// Rewrites castling entered as the king capturing its own rook (`e1h1`) as
+/// the usual two-square king move (`e1g1`); any other move is returned as is.
+fn king_takes_rook_castle(board: &Board, mv: ChessMove) -> ChessMove {
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let mover = board.side_to_move();
+    let own = |square| board.color_on(square) == Some(mover);
+    if board.piece_on(source) != Some(Piece::King)
+        || board.piece_on(dest) != Some(Piece::Rook)
+        || !own(source)
+        || !own(dest)
+        || source.get_rank() != dest.get_rank()
+    {
+        return mv;
+    }
+    let king_file = if dest.get_file() > source.get_file() {
+        File::G
+    } else {
+        File::C
+    };
+    ChessMove::new(
+        source,
+        Square::make_square(source.get_rank(), king_file),
+        None,
+    )
+}
+
+/// Validates a client-supplied promotion piece: a single letter "q", "r",
+/// "b" or "n" in either case, returned lowercase. Blank input means none.
+pub fn normalize_promotion(promotion: Option<&str>) -> Result<Option<char>, ChainChessError> {
+    let Some(raw) = promotion.map(str::trim).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    let mut chars = raw.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if promotion_piece(letter).is_some() => {
+            Ok(Some(letter.to_ascii_lowercase()))
+        }
+        _ => Err(ChainChessError::InvalidMove(
+            "invalid promotion piece".into(),
+        )),
+    }
+}
+
+/// Human-readable reason `uci` cannot be played in `fen`, checked from the
+/// coarsest problem to the finest; legal moves report "move is legal".
+pub fn explain_illegal(fen: &str, uci: &str) -> String {
+    let Ok(board) = Board::from_str(fen) else {
+        return "invalid position".into();
+    };
+    let Ok(mv) = parse_uci_move(&uci.trim().to_ascii_lowercase()) else {
+        return "malformed move".into();
+    };
+    let mv = king_takes_rook_castle(&board, mv);
+    let reason = if board.legal(mv) {
+        "move is legal"
+    } else if board.piece_on(mv.get_source()).is_none() {
+        "no piece on source"
+    } else if board.color_on(mv.get_source()) != Some(board.side_to_move()) {
+        "not that side's turn"
+    } else if board.color_on(mv.get_dest()) == Some(board.side_to_move()) {
+        "destination occupied by own piece"
+    } else if !is_pseudo_legal(&board, mv) {
+        "move not pseudo-legal for piece"
+    } else {
+        "leaves king in check"
+    };
+    reason.into()
+}
+
+/// Whether `mv` follows the movement rules of the side to move's piece on its
+/// source square, ignoring whether the king is left in check.
+fn is_pseudo_legal(board: &Board, mv: ChessMove) -> bool {
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let color = board.side_to_move();
+    let blockers = *board.combined();
+    let Some(piece) = board.piece_on(source) else {
+        return false;
+    };
+    let promotes = piece == Piece::Pawn && dest.get_rank() == color.to_their_backrank();
+    if promotes != mv.get_promotion().is_some() {
+        return false;
+    }
+    let targets = match piece {
+        Piece::Pawn => {
+            let mut victims = *board.color_combined(!color);
+            if let Some(pawn) = board.en_passant() {
+                victims |= BitBoard::from_square(pawn.uforward(color));
+            }
+            get_pawn_attacks(source, color, victims) | get_pawn_quiets(source, color, blockers)
+        }
+        Piece::Knight => get_knight_moves(source),
+        Piece::Bishop => get_bishop_moves(source, blockers),
+        Piece::Rook => get_rook_moves(source, blockers),
+        Piece::Queen => get_bishop_moves(source, blockers) | get_rook_moves(source, blockers),
+        Piece::King => {
+            let back_rank = color.to_my_backrank();
+            let castles = source == Square::make_square(back_rank, File::E)
+                && dest.get_rank() == back_rank
+                && [(File::G, File::H), (File::C, File::A)].into_iter().any(
+                    |(king_file, rook_file)| {
+                        let rights = board.my_castle_rights();
+                        let allowed = if rook_file == File::H {
+                            rights.has_kingside()
+                        } else {
+                            rights.has_queenside()
+                        };
+                        let rook = Square::make_square(back_rank, rook_file);
+                        dest.get_file() == king_file
+                            && allowed
+                            && (between(source, rook) & blockers).popcnt() == 0
+                    },
+                );
+            if castles {
+                return true;
+            }
+            get_king_moves(source)
+        }
+    };
+    targets & BitBoard::from_square(dest) != BitBoard::new(0)
+}
+
+pub fn promotion_piece(letter: char) -> Option<Piece> {
+    match letter.to_ascii_lowercase() {
+        'q' => Some(Piece::Queen),
+        'r' => Some(Piece::Rook),
+        'b' => Some(Piece::Bishop),
+        'n' => Some(Piece::Knight),
+        _ => None,
+    }
+}
+
+fn illegal_move() -> ChainChessError {
+    ChainChessError::InvalidMove("move is illegal in current position".into())
+}
+
+/// Material value of a piece in pawns; the king is not counted.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 0,
+    }
+}
+
+/// Legal promotions for the pawn move `from_to` (e.g. "e7e8") in `fen`.
+///
+/// Options are ordered queen, rook, bishop, knight and flagged when they give
+/// check, mate or stalemate. Returns `None` when the move is not a promotion.
+pub fn promotion_options(fen: &str, from_to: &str) -> Option<PromotionOptions> {
+    let board = Board::from_str(fen).ok()?;
+    let base = parse_uci_move(from_to.get(0..4)?).ok()?;
+    let mut ranked = Vec::new();
+    for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+        let mv = ChessMove::new(base.get_source(), base.get_dest(), Some(piece));
+        if !board.legal(mv) {
+            continue;
+        }
+        let after = board.make_move_new(mv);
+        let status = after.status();
+        let uci = move_to_uci_string(mv);
+        let option = PromotionOption {
+            piece: uci[4..].to_string(),
+            uci,
+            gives_check: after.checkers().popcnt() > 0,
+            gives_mate: status == BoardStatus::Checkmate,
+            stalemates: status == BoardStatus::Stalemate,
+        };
+        let score = if option.gives_mate {
+            i32::MAX
+        } else if option.stalemates {
+            i32::MIN
+        } else {
+            piece_value(piece)
+        };
+        ranked.push((score, option));
+    }
+    // `max_by_key` keeps the last maximum, so reverse to let ties favour the stronger piece.
+    let best = ranked
+        .iter()
+        .rev()
+        .max_by_key(|(score, _)| *score)?
+        .1
+        .uci
+        .clone();
+    Some(PromotionOptions {
+        options: ranked.into_iter().map(|(_, option)| option).collect(),
+        best,
+    })
+}
+
+/// Standard chess starting position.
+pub const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Longest movetext line of an exported PGN, as the export format asks.
+const PGN_LINE_WIDTH: usize = 80;
+
+/// Renders a game as PGN: the given tag pairs, `SetUp`/`FEN` tags when the
+/// game did not start from the standard position, then the numbered SAN
+/// moves followed by `result`.
+pub fn encode_pgn<'a>(
+    tags: &[(&str, String)],
+    initial_fen: &str,
+    sans: impl IntoIterator<Item = &'a str>,
+    result: &str,
+) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+    }
+    if initial_fen != DEFAULT_FEN {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{initial_fen}\"]\n"));
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+    let mut number = move_counters(initial_fen).1.max(1);
+    let mut white_to_move = side_to_move(initial_fen) != Some(PlayerColor::Black);
+    for (index, san) in sans.into_iter().enumerate() {
+        if white_to_move {
+            tokens.push(format!("{number}."));
+        } else if index == 0 {
+            tokens.push(format!("{number}..."));
+        }
+        tokens.push(san.to_string());
+        if !white_to_move {
+            number += 1;
+        }
+        white_to_move = !white_to_move;
+    }
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_WIDTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+/// `YYYY.MM.DD` date, as used by the PGN `Date` tag, of a time given in
+/// microseconds since the Unix epoch.
+pub fn pgn_date(micros: u64) -> String {
+    // Civil-from-days conversion for the proleptic Gregorian calendar.
+    let days = (micros / 86_400_000_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+/// Encodes a starting position and its UCI moves as a short URL-safe code.
+///
+/// The payload is the BCS encoding of the FEN followed by one `u16` per move
+/// (source square, destination square and promotion piece packed together).
+pub fn encode_share_code<'a>(
+    initial_fen: &str,
+    moves: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let packed = moves
+        .into_iter()
+        .map(|uci| parse_uci_move(uci).ok().map(pack_move))
+        .collect::<Option<Vec<u16>>>()?;
+    let bytes = bcs::to_bytes(&(initial_fen, packed)).ok()?;
+    Some(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Inverse of [`encode_share_code`]: the starting FEN and the UCI move list.
+pub fn decode_share_code(code: &str) -> Option<(String, Vec<String>)> {
+    let bytes = URL_SAFE_NO_PAD.decode(code.trim()).ok()?;
+    let (fen, packed): (String, Vec<u16>) = bcs::from_bytes(&bytes).ok()?;
+    let moves = packed
+        .into_iter()
+        .map(|value| unpack_move(value).map(move_to_uci_string))
+        .collect::<Option<Vec<_>>>()?;
+    Some((fen, moves))
+}
+
+const PROMOTION_CODES: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+fn pack_move(mv: ChessMove) -> u16 {
+    let promotion = mv
+        .get_promotion()
+        .and_then(|piece| PROMOTION_CODES.iter().position(|code| *code == piece))
+        .map_or(0, |index| index as u16 + 1);
+    mv.get_source().to_index() as u16 | (mv.get_dest().to_index() as u16) << 6 | promotion << 12
+}
+
+fn unpack_move(value: u16) -> Option<ChessMove> {
+    let square = |index: u16| ALL_SQUARES[(index & 0x3f) as usize];
+    let promotion = match value >> 12 {
+        0 => None,
+        code => Some(*PROMOTION_CODES.get(code as usize - 1)?),
+    };
+    Some(ChessMove::new(square(value), square(value >> 6), promotion))
+}
+
+pub fn move_to_uci_string(mv: ChessMove) -> String {
+    let mut result = format!("{}{}", mv.get_source(), mv.get_dest());
+    if let Some(promo) = mv.get_promotion() {
+        let letter = match promo {
+            Piece::Queen => 'q',
+            Piece::Rook => 'r',
+            Piece::Bishop => 'b',
+            Piece::Knight => 'n',
+            _ => 'q',
+        };
+        result.push(letter);
+    }
+    result
+}
+
+pub fn generate_san(board: &Board, mv: ChessMove) -> String {
+    let mut san = san_body(board, mv);
+    let after = board.make_move_new(mv);
+    if after.status() == BoardStatus::Checkmate {
+        san.push('#');
+    } else if after.checkers().popcnt() > 0 {
+        san.push('+');
+    }
+    san
+}
+
+/// SAN of `mv` without the check or mate suffix.
+fn san_body(board: &Board, mv: ChessMove) -> String {
+    // Simple SAN generation - in a production system you'd use a proper chess library
+    let from = mv.get_source();
+    let to = mv.get_dest();
+    let piece = board.piece_on(from);
+
+    // Castling is the only king move spanning two files.
+    if piece == Some(Piece::King)
+        && from
+            .get_file()
+            .to_index()
+            .abs_diff(to.get_file().to_index())
+            == 2
+    {
+        return if to.get_file() == File::G {
+            "O-O".to_string()
+        } else {
+            "O-O-O".to_string()
+        };
+    }
+
+    let piece_char = match piece {
+        Some(Piece::King) => 'K',
+        Some(Piece::Queen) => 'Q',
+        Some(Piece::Rook) => 'R',
+        Some(Piece::Bishop) => 'B',
+        Some(Piece::Knight) => 'N',
+        _ => ' ',
+    };
+
+    let from_sq = format!("{}", from);
+    let to_sq = format!("{}", to);
+
+    if piece_char != ' ' {
+        let capture = if board.piece_on(to).is_some() {
+            "x"
+        } else {
+            ""
+        };
+        let from_hint = disambiguation(board, mv);
+        return format!("{piece_char}{from_hint}{capture}{to_sq}");
+    }
+
+    // Pawn moves: the file of origin only on captures, including en passant,
+    // whose landing square is the destination.
+    let mut san = if captured_piece(board, mv).is_some() {
+        format!("{}x{}", &from_sq[..1], to_sq)
+    } else {
+        to_sq
+    };
+    if let Some(promo) = mv.get_promotion() {
+        let promo_char = match promo {
+            Piece::Queen => 'Q',
+            Piece::Rook => 'R',
+            Piece::Bishop => 'B',
+            Piece::Knight => 'N',
+            _ => 'Q',
+        };
+        san.push('=');
+        san.push(promo_char);
+    }
+    san
+}
+
+/// Part of the source square SAN needs to tell `mv` apart from other legal
+/// moves of the same piece type to the same square: nothing, the file, the
+/// rank, or the whole square, in that order of preference.
+fn disambiguation(board: &Board, mv: ChessMove) -> String {
+    let from = mv.get_source();
+    let rivals: Vec<Square> = MoveGen::new_legal(board)
+        .filter(|other| {
+            other.get_dest() == mv.get_dest()
+                && other.get_source() != from
+                && board.piece_on(other.get_source()) == board.piece_on(from)
+        })
+        .map(|other| other.get_source())
+        .collect();
+    let square = from.to_string();
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals
+        .iter()
+        .all(|rival| rival.get_file() != from.get_file())
+    {
+        square[..1].to_string()
+    } else if rivals
+        .iter()
+        .all(|rival| rival.get_rank() != from.get_rank())
+    {
+        square[1..].to_string()
+    } else {
+        square
+    }
+}
+
+/// Book replies for the first plies of a few mainlines, keyed by
+/// [`position_key`]. Covers both colors so training games open sensibly too.
+const OPENING_BOOK: &[(&str, &str)] = &[
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        "e2e4",
+    ),
+    (
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -",
+        "e7e5",
+    ),
+    (
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -",
+        "g1f3",
+    ),
+    (
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq -",
+        "b8c6",
+    ),
+    (
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -",
+        "f1b5",
+    ),
+    (
+        "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq -",
+        "a7a6",
+    ),
+    (
+        "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq -",
+        "f8c5",
+    ),
+    (
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -",
+        "g1f3",
+    ),
+    (
+        "rnbqkbnr/pppp1ppp/4p3/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -",
+        "d2d4",
+    ),
+    (
+        "rnbqkbnr/pp1ppppp/2p5/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -",
+        "d2d4",
+    ),
+    (
+        "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq -",
+        "d7d5",
+    ),
+    (
+        "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq -",
+        "c2c4",
+    ),
+    (
+        "rnbqkbnr/ppp1pppp/8/3p4/2PP4/8/PP2PPPP/RNBQKBNR b KQkq -",
+        "e7e6",
+    ),
+    (
+        "rnbqkb1r/pppppppp/5n2/8/3P4/8/PPP1PPPP/RNBQKBNR w KQkq -",
+        "c2c4",
+    ),
+    (
+        "rnbqkbnr/pppppppp/8/8/2P5/8/PP1PPPPP/RNBQKBNR b KQkq -",
+        "e7e5",
+    ),
+    (
+        "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq -",
+        "d7d5",
+    ),
+];
+
+/// The opening book's reply in `fen`, if the position is in it.
+fn book_move(fen: &str) -> Option<&'static str> {
+    let key = position_key(fen);
+    OPENING_BOOK
+        .iter()
+        .find(|(position, _)| *position == key)
+        .map(|(_, reply)| *reply)
+}
+
+/// Chooses the AI reply for `fen`: the opening book's move when it has one,
+/// otherwise the best move of a `depth`-ply minimax whose leaf evaluation is
+/// weighted by the personality (see [`personality_score`]), captures and
+/// central squares breaking near-ties. Moves that still score the same are
+/// ordered by source then destination square and picked between with
+/// `seed`, so the choice can be reproduced from a recorded
+/// [`crate::AiMoveTrace`] on any node.
+pub fn pick_ai_move(fen: &str, seed: u64, depth: u8, personality: Personality) -> Option<String> {
+    if let Some(reply) = book_move(fen) {
+        return Some(reply.to_string());
+    }
+    let board = Board::from_str(fen).ok()?;
+    let style = Style {
+        personality,
+        side: board.side_to_move(),
+    };
+    let mut best_moves = Vec::new();
+    let mut best_score = -SEARCH_MATE_SCORE * 4;
+    for mv in MoveGen::new_legal(&board) {
+        let bonus = score_move(&board, mv);
+        // Only moves that could reach `best_score` need an exact score.
+        let alpha = best_score - bonus - 1;
+        let score = bonus
+            - minimax(
+                &board.make_move_new(mv),
+                depth.max(1) - 1,
+                -SEARCH_MATE_SCORE * 2,
+                -alpha,
+                style,
+            );
+        if score > best_score {
+            best_score = score;
+            best_moves.clear();
+        }
+        if score == best_score {
+            best_moves.push(mv);
+        }
+    }
+    if best_moves.is_empty() {
+        return None;
+    }
+    // Order the tied moves by squares so the pick never depends on the
+    // move generator's iteration order.
+    best_moves.sort_by_key(|mv| {
+        (
+            mv.get_source().to_index(),
+            mv.get_dest().to_index(),
+            mv.get_promotion().map(|piece| piece.to_index()),
+        )
+    });
+    let index = (seed % best_moves.len() as u64) as usize;
+    Some(move_to_uci_string(best_moves[index]))
+}
+
+/// Seed for the AI move played at `ply` of a game seeded with `game_seed`.
+pub fn ai_move_seed(game_seed: u64, ply: usize) -> u64 {
+    mix_seed(game_seed ^ ply as u64)
+}
+
+/// SplitMix64 finalizer; spreads nearby inputs over the whole `u64` range.
+pub fn mix_seed(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Static evaluation in centipawns from White's point of view: material plus a
+/// small bonus for pieces (other than the king) standing in the centre.
+pub fn evaluate(board: &Board) -> i32 {
+    let mut score = 0;
+    for square in *board.combined() {
+        let (Some(piece), Some(color)) = (board.piece_on(square), board.color_on(square)) else {
+            continue;
+        };
+        let mut value = piece_value(piece) * 100;
+        if piece != Piece::King {
+            value += square_bonus(square) * 10;
+        }
+        score += if color == Color::White { value } else { -value };
+    }
+    score
+}
+
+/// [`evaluate`] of the position in `fen`, or `None` if it does not parse.
+pub fn evaluate_fen(fen: &str) -> Option<i32> {
+    Board::from_str(fen).ok().map(|board| evaluate(&board))
+}
+
+/// Thresholds the AI uses when judging whole positions.
+#[derive(Clone, Copy, Debug)]
+pub struct EvalConfig {
+    /// Plies after which the AI agrees a draw in a dead-equal position.
+    pub auto_draw_min_plies: usize,
+    /// Largest absolute evaluation, in centipawns, still considered equal.
+    pub auto_draw_max_eval: i32,
+}
+
+impl EvalConfig {
+    pub const DEFAULT: Self = Self {
+        auto_draw_min_plies: 200,
+        auto_draw_max_eval: 50,
+    };
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Whether the AI, about to move in `fen` after `plies` half-moves, would
+/// rather agree a draw than play on.
+pub fn ai_accepts_draw(fen: &str, plies: usize, config: &EvalConfig) -> bool {
+    plies >= config.auto_draw_min_plies
+        && Board::from_str(fen)
+            .is_ok_and(|board| evaluate(&board).abs() <= config.auto_draw_max_eval)
+}
+
+/// Longest game, in plies, that [`eval_history`] will replay.
+pub const MAX_EVAL_HISTORY_PLIES: usize = 512;
+
+/// Replays `moves` from `initial_fen` and returns [`evaluate`] after each ply.
+///
+/// Cost grows linearly with the game length, so only the first
+/// [`MAX_EVAL_HISTORY_PLIES`] plies are replayed. Replay stops early at a move
+/// that is not legal in the replayed position.
+pub fn eval_history<'a>(initial_fen: &str, moves: impl IntoIterator<Item = &'a str>) -> Vec<i32> {
+    let mut evals = Vec::new();
+    let mut fen = initial_fen.to_string();
+    for uci in moves.into_iter().take(MAX_EVAL_HISTORY_PLIES) {
+        let Ok(outcome) = apply_uci_move(&fen, uci, None) else {
+            break;
+        };
+        fen = outcome.fen;
+        match Board::from_str(&fen) {
+            Ok(board) => evals.push(evaluate(&board)),
+            Err(_) => break,
+        }
+    }
+    evals
+}
+
+/// Position reached from `initial_fen` after playing `moves`, or `None` if
+/// any move is illegal along the way.
+pub fn replay_fen<'a>(
+    initial_fen: &str,
+    moves: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let mut fen = initial_fen.to_string();
+    for uci in moves {
+        fen = apply_uci_move(&fen, uci, None).ok()?.fen;
+    }
+    Some(fen)
+}
+
+/// Part of a FEN that identifies a position for repetition: placement, side
+/// to move, castling rights and en passant square, without the move counters.
+pub fn position_key(fen: &str) -> String {
+    fen.split_whitespace().take(4).collect::<Vec<_>>().join(" ")
+}
+
+/// How many times the position reached after `moves` from `initial_fen`
+/// has occurred in the game, counting the initial position. Replay stops at
+/// the first illegal move.
+pub fn repetition_count<'a>(initial_fen: &str, moves: impl IntoIterator<Item = &'a str>) -> usize {
+    let mut fen = initial_fen.to_string();
+    let mut keys = vec![position_key(&fen)];
+    for uci in moves {
+        let Ok(outcome) = apply_uci_move(&fen, uci, None) else {
+            break;
+        };
+        fen = outcome.fen;
+        keys.push(position_key(&fen));
+    }
+    let last = keys.last().cloned().unwrap_or_default();
+    keys.iter().filter(|key| **key == last).count()
+}
+
+/// Deepest mate, in moves of the side to move, `forced_mate` searches for.
+/// The search is exhaustive, so each extra move multiplies its cost.
+pub const MAX_MATE_SEARCH_DEPTH: u8 = 3;
+
+/// Length of the shortest forced mate for the side to move, in its own moves,
+/// searching at most `max_depth` (capped at `MAX_MATE_SEARCH_DEPTH`) moves.
+pub fn forced_mate(fen: &str, max_depth: u8) -> Option<u8> {
+    let board = Board::from_str(fen).ok()?;
+    (1..=max_depth.min(MAX_MATE_SEARCH_DEPTH)).find(|&moves| mates_within(&board, moves))
+}
+
+/// Whether the side to move can force checkmate in at most `moves` moves.
+fn mates_within(board: &Board, moves: u8) -> bool {
+    MoveGen::new_legal(board).any(|mv| {
+        let after = board.make_move_new(mv);
+        match after.status() {
+            BoardStatus::Checkmate => true,
+            BoardStatus::Stalemate => false,
+            BoardStatus::Ongoing => {
+                moves > 1
+                    && MoveGen::new_legal(&after)
+                        .all(|reply| mates_within(&after.make_move_new(reply), moves - 1))
+            }
+        }
+    })
+}
+
+/// Material, in pawns, a move may lose over the following exchange before
+/// `blunder_warning` flags it.
+const BLUNDER_MARGIN: i32 = 2;
+
+/// Warning for a legal `uci` in `fen` that lets the opponent force mate
+/// within two moves or loses at least `BLUNDER_MARGIN` pawns of material once
+/// the opponent's best capture and the mover's best reply are played out.
+/// Returns `None` for sound moves and for moves that are not legal.
+pub fn blunder_warning(fen: &str, uci: &str) -> Option<String> {
+    let board = Board::from_str(fen).ok()?;
+    let mover = board.side_to_move();
+    let outcome = apply_uci_move(fen, uci, None).ok()?;
+    if outcome.result.is_some() {
+        return None;
+    }
+    if let Some(moves) = forced_mate(&outcome.fen, 2) {
+        return Some(format!("this move allows mate in {moves}"));
+    }
+    let after = Board::from_str(&outcome.fen).ok()?;
+    // Worst case over the opponent's replies of the mover's best answer.
+    let worst = MoveGen::new_legal(&after)
+        .map(|reply| {
+            let position = after.make_move_new(reply);
+            MoveGen::new_legal(&position)
+                .map(|answer| material_balance(&position.make_move_new(answer), mover))
+                .max()
+                .unwrap_or_else(|| material_balance(&position, mover))
+        })
+        .min()
+        .unwrap_or_else(|| material_balance(&after, mover));
+    let lost = material_balance(&board, mover) - worst;
+    (lost >= BLUNDER_MARGIN).then(|| format!("this move loses about {lost} pawns of material"))
+}
+
+/// Material of `color` minus that of its opponent, in pawns.
+fn material_balance(board: &Board, color: Color) -> i32 {
+    let mut balance = 0;
+    for square in *board.combined() {
+        if let Some(piece) = board.piece_on(square) {
+            let value = piece_value(piece);
+            balance += if board.color_on(square) == Some(color) {
+                value
+            } else {
+                -value
+            };
+        }
+    }
+    balance
+}
+
+/// Deepest line, in plies, `principal_variation` searches.
+pub const MAX_PV_DEPTH: u8 = 4;
+/// Search score of being checkmated, beyond any material evaluation.
+const SEARCH_MATE_SCORE: i32 = 1_000_000;
+
+/// Best line of play from `fen` found by a `depth`-ply (capped at
+/// `MAX_PV_DEPTH`) alpha-beta negamax over `evaluate`, as UCI moves.
+pub fn principal_variation(fen: &str, depth: u8) -> Vec<String> {
+    let Ok(board) = Board::from_str(fen) else {
+        return Vec::new();
+    };
+    let style = Style {
+        personality: Personality::Balanced,
+        side: board.side_to_move(),
+    };
+    let mut pv = Vec::new();
+    negamax(
+        &board,
+        depth.min(MAX_PV_DEPTH),
+        -SEARCH_MATE_SCORE * 2,
+        SEARCH_MATE_SCORE * 2,
+        style,
+        &mut pv,
+    );
+    pv.into_iter().map(move_to_uci_string).collect()
+}
+
+/// The searching side and its personality, which together weight the
+/// evaluation at the leaves of the search.
+#[derive(Clone, Copy)]
+struct Style {
+    personality: Personality,
+    side: Color,
+}
+
+/// Alpha-beta score of `board` for the side to move, searched `depth` plies:
+/// a checkmate scores beyond any material, a stalemate zero.
+fn minimax(board: &Board, depth: u8, alpha: i32, beta: i32, style: Style) -> i32 {
+    negamax(board, depth, alpha, beta, style, &mut Vec::new())
+}
+
+/// Score of `board` for the side to move, filling `pv` with the best line.
+/// Mates found with more depth left, i.e. sooner, score further from zero.
+fn negamax(
+    board: &Board,
+    depth: u8,
+    mut alpha: i32,
+    beta: i32,
+    style: Style,
+    pv: &mut Vec<ChessMove>,
+) -> i32 {
+    pv.clear();
+    match board.status() {
+        BoardStatus::Checkmate => return -SEARCH_MATE_SCORE - i32::from(depth),
+        BoardStatus::Stalemate => return 0,
+        BoardStatus::Ongoing => {}
+    }
+    if depth == 0 {
+        let score = evaluate(board);
+        let score = if board.side_to_move() == Color::White {
+            score
+        } else {
+            -score
+        };
+        let style_score = personality_score(board, style.personality, style.side);
+        return if board.side_to_move() == style.side {
+            score + style_score
+        } else {
+            score - style_score
+        };
+    }
+    let mut line = Vec::new();
+    let mut best = -SEARCH_MATE_SCORE * 2;
+    for mv in MoveGen::new_legal(board) {
+        let score = -negamax(
+            &board.make_move_new(mv),
+            depth - 1,
+            -beta,
+            -alpha,
+            style,
+            &mut line,
+        );
+        if score > best {
+            best = score;
+            pv.clear();
+            pv.push(mv);
+            pv.extend_from_slice(&line);
+        }
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Greedy score awarded to a mating move, above any capture.
+const MATE_MOVE_SCORE: i32 = 1_000;
+/// Greedy score magnitude of a stalemating move: a bonus when seeking it, a
+/// penalty when avoiding it.
+const STALEMATE_MOVE_SCORE: i32 = 500;
+/// Material deficit, in centipawns, below which the AI treats stalemate as a save.
+const STALEMATE_SEEK_DEFICIT: i32 = 300;
+
+/// Terminal positions are scored relative to the side to move: a draw is a
+/// good result for a side that is clearly losing and a bad one otherwise.
+fn terminal_move_score(board: &Board, after: &Board) -> Option<i32> {
+    match after.status() {
+        BoardStatus::Ongoing => None,
+        BoardStatus::Checkmate => Some(MATE_MOVE_SCORE),
+        BoardStatus::Stalemate => {
+            let eval = evaluate(board);
+            let mover_eval = if board.side_to_move() == Color::White {
+                eval
+            } else {
+                -eval
+            };
+            if mover_eval <= -STALEMATE_SEEK_DEFICIT {
+                Some(STALEMATE_MOVE_SCORE)
+            } else {
+                Some(-STALEMATE_MOVE_SCORE)
+            }
+        }
+    }
+}
+
+/// Centipawns an aggressive AI adds for the enemy king standing in check.
+const AGGRESSIVE_CHECK_WEIGHT: i32 = 150;
+/// Centipawns an aggressive AI adds per attacked square next to the enemy king.
+const AGGRESSIVE_ATTACK_WEIGHT: i32 = 15;
+/// Centipawns a defensive AI subtracts per attacked square next to its own king.
+const DEFENSIVE_EXPOSURE_WEIGHT: i32 = 25;
+
+/// Personality adjustment to the evaluation of `board`, in centipawns for
+/// `side`: an aggressive AI values pressure on the enemy king, a defensive
+/// one safety around its own, and a balanced one neither.
+fn personality_score(board: &Board, personality: Personality, side: Color) -> i32 {
+    match personality {
+        Personality::Aggressive => {
+            let enemy_king = board.king_square(!side);
+            let check = if attackers(board, enemy_king, side).popcnt() > 0 {
+                AGGRESSIVE_CHECK_WEIGHT
+            } else {
+                0
+            };
+            check + AGGRESSIVE_ATTACK_WEIGHT * king_exposure(board, !side)
+        }
+        Personality::Defensive => -DEFENSIVE_EXPOSURE_WEIGHT * king_exposure(board, side),
+        Personality::Balanced => 0,
+    }
+}
+
+fn score_move(board: &Board, mv: ChessMove) -> i32 {
+    let after = board.make_move_new(mv);
+    if let Some(score) = terminal_move_score(board, &after) {
+        return score;
+    }
+    let mut score = 0;
+    if let Some(piece) = board.piece_on(mv.get_dest()) {
+        score += piece_value(piece);
+    }
+    if mv.get_promotion().is_some() {
+        score += 5;
+    }
+    score += square_bonus(mv.get_dest());
+    score
+}
+
+/// Squares next to `color`'s king that the opponent attacks.
+fn king_exposure(board: &Board, color: Color) -> i32 {
+    get_king_moves(board.king_square(color))
+        .filter(|square| attackers(board, *square, !color).popcnt() > 0)
+        .count() as i32
+}
+
+fn square_bonus(square: Square) -> i32 {
+    let file = square.get_file().to_index() as i32;
+    let rank = square.get_rank().to_index() as i32;
+    if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
+        2
+    } else if (2..=5).contains(&file) && (2..=5).contains(&rank) {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn san(board: &Board, uci: &str) -> String {
+        generate_san(board, parse_uci_move(uci).unwrap())
+    }
+
+    #[test]
+    fn under_promotion_to_knight_is_best_when_it_mates() {
+        let options = promotion_options("5bnb/4Ppkp/5ppp/8/8/8/8/K7 w - - 0 1", "e7e8").unwrap();
+        assert_eq!(options.best, "e7e8n");
+        let knight = options
+            .options
+            .iter()
+            .find(|option| option.piece == "n")
+            .unwrap();
+        assert!(knight.gives_mate);
+        assert!(promotion_options(START, "e2e4").is_none());
+    }
+
+    #[test]
+    fn board_unicode_renders_the_starting_position() {
+        let board = board_unicode(START).unwrap();
+        let lines: Vec<&str> = board.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with('8'));
+        assert!(lines[0].contains('♚'));
+        assert!(lines[7].starts_with('1'));
+        assert!(lines[7].contains('♔'));
+        assert_eq!(lines[6].matches('♙').count(), 8);
+    }
+
+    #[test]
+    fn eval_history_returns_one_eval_per_ply() {
+        let evals = eval_history(START, ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(evals.len(), 3);
+        assert!(eval_history(START, ["e2e4", "e2e4"]).len() == 1);
+    }
+
+    #[test]
+    fn en_passant_capture_records_the_pawn() {
+        let outcome = apply_uci_move(
+            "rnbqkbnr/ppp2ppp/4p3/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "e5d6",
+            None,
+        )
+        .unwrap();
+        assert_eq!(outcome.captured, Some(PieceKind::Pawn));
+    }
+
+    #[test]
+    fn share_codes_round_trip() {
+        let code = encode_share_code(START, ["e2e4", "e7e5", "a7a8q"]).unwrap();
+        assert_eq!(
+            decode_share_code(&code).unwrap(),
+            (
+                START.to_string(),
+                vec!["e2e4".to_string(), "e7e5".into(), "a7a8q".into()]
+            )
+        );
+    }
+
+    #[test]
+    fn hanging_pieces_finds_an_undefended_attacked_queen() {
+        assert_eq!(
+            hanging_pieces("4k3/8/2n5/8/3Q4/8/8/4K3 w - - 0 1"),
+            vec!["d4".to_string()]
+        );
+        assert!(hanging_pieces(START).is_empty());
+    }
+
+    #[test]
+    fn padded_uci_is_accepted_like_the_clean_form() {
+        let clean = apply_uci_move(START, "e2e4", None).unwrap();
+        let padded = apply_uci_move(START, "  e2e4\n", None).unwrap();
+        assert_eq!(padded.uci, clean.uci);
+        assert_eq!(padded.fen, clean.fen);
+    }
+
+    #[test]
+    fn promotion_letters_are_normalized() {
+        let fen = "8/P7/8/8/8/8/8/k6K w - - 0 1";
+        assert_eq!(apply_uci_move(fen, "a7a8", Some("Q")).unwrap().uci, "a7a8q");
+        assert_eq!(apply_uci_move(fen, "a7a8", Some("n")).unwrap().uci, "a7a8n");
+        assert!(apply_uci_move(fen, "a7a8", Some("queen")).is_err());
+    }
+
+    #[test]
+    fn explain_illegal_names_each_reason() {
+        assert_eq!(explain_illegal(START, "e2e4"), "move is legal");
+        assert_eq!(explain_illegal(START, "e3e4"), "no piece on source");
+        assert_eq!(explain_illegal(START, "e7e5"), "not that side's turn");
+        assert_eq!(
+            explain_illegal(START, "a1a2"),
+            "destination occupied by own piece"
+        );
+        assert_eq!(
+            explain_illegal(START, "b1b3"),
+            "move not pseudo-legal for piece"
+        );
+        assert_eq!(
+            explain_illegal("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1", "e2d3"),
+            "leaves king in check"
+        );
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(explain_illegal(castling, "e1g1"), "move is legal");
+        assert_eq!(explain_illegal(castling, "e1c1"), "move is legal");
+        assert_eq!(
+            explain_illegal("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"),
+            "leaves king in check"
+        );
+        assert_eq!(
+            explain_illegal(
+                "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1",
+                "e5d6"
+            ),
+            "move is legal"
+        );
+        assert_eq!(explain_illegal(START, "zz"), "malformed move");
+    }
+
+    #[test]
+    fn forced_mate_finds_short_mates() {
+        assert_eq!(forced_mate("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3), Some(1));
+        assert_eq!(forced_mate("k7/8/2K5/8/8/8/8/7R w - - 0 1", 3), Some(2));
+        assert_eq!(forced_mate(START, 2), None);
+    }
+
+    #[test]
+    fn search_takes_material_and_finds_mate() {
+        let fen = "7k/8/8/7n/r7/8/8/2KQ4 w - - 0 1";
+        assert_eq!(
+            pick_ai_move(fen, 0, 3, Personality::Balanced).unwrap(),
+            "d1a4"
+        );
+        let back_rank = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(
+            pick_ai_move(back_rank, 0, 3, Personality::Balanced).unwrap(),
+            "a1a8"
+        );
+        // The d5 pawn is defended, so the queen must not grab it.
+        let defended = "4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1";
+        assert_ne!(
+            pick_ai_move(defended, 0, 4, Personality::Balanced).unwrap(),
+            "d2d5"
+        );
+    }
+
+    #[test]
+    fn aggressive_ai_prefers_a_checking_capture() {
+        // Either knight can be taken; only Qxa4 gives check.
+        let fen = "4k3/8/8/8/n2n4/8/8/3Q3K w - - 0 1";
+        assert_eq!(
+            pick_ai_move(fen, 0, AI_SEARCH_DEPTH, Personality::Balanced).unwrap(),
+            "d1d4"
+        );
+        assert_eq!(
+            pick_ai_move(fen, 0, AI_SEARCH_DEPTH, Personality::Aggressive).unwrap(),
+            "d1a4"
+        );
+    }
+
+    #[test]
+    fn lost_ai_sacrifices_into_stalemate() {
+        // After Qg2+ Kxg2 or Qh1+ Kxh1, Black has no legal move.
+        let fen = "q6k/5Q1p/7P/8/8/8/8/2R3K1 b - - 0 1";
+        let reply = pick_ai_move(fen, 0, AI_SEARCH_DEPTH, Personality::Balanced).unwrap();
+        assert!(["a8g2", "a8h1"].contains(&reply.as_str()), "{reply}");
+    }
+
+    #[test]
+    fn winning_ai_avoids_stalemate() {
+        // Qxb6 wins the last pawn but stalemates the black king.
+        let fen = "k7/2K5/1p6/8/8/8/8/1Q6 w - - 0 1";
+        let reply = pick_ai_move(fen, 0, AI_SEARCH_DEPTH, Personality::Balanced).unwrap();
+        assert_ne!(reply, "b1b6");
+        let after = apply_uci_move(fen, &reply, None).unwrap();
+        assert!(!is_stalemate(&after.fen));
+    }
+
+    #[test]
+    fn ai_accepts_draws_only_in_long_equal_games() {
+        let config = EvalConfig::DEFAULT;
+        let bare_kings = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+        assert!(!ai_accepts_draw(bare_kings, 199, &config));
+        assert!(ai_accepts_draw(bare_kings, 200, &config));
+        assert!(ai_accepts_draw(bare_kings, 299, &config));
+        let extra_queen = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1";
+        assert!(!ai_accepts_draw(extra_queen, 250, &config));
+    }
+
+    #[test]
+    fn mate_on_the_hundredth_halfmove_is_a_win() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80";
+        let outcome = apply_uci_move(fen, "a1a8", None).unwrap();
+        assert_eq!(move_counters(&outcome.fen), (100, 80));
+        assert_eq!(
+            outcome.result,
+            Some(MatchResult::Winner(
+                PlayerColor::White,
+                TerminationReason::Checkmate
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_fen_rejects_the_side_not_to_move_in_check() {
+        assert!(validate_fen(START).is_ok());
+        let error = validate_fen("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid FEN: the side not to move is in check"
+        );
+        assert!(validate_fen("garbage").is_err());
+    }
+
+    #[test]
+    fn move_counters_follow_quiet_moves() {
+        let mut fen = START.to_string();
+        for uci in ["g1f3", "g8f6", "b1c3", "b8c6", "e2e4"] {
+            fen = apply_uci_move(&fen, uci, None).unwrap().fen;
+        }
+        assert_eq!(move_counters(&fen), (0, 3));
+        let after = apply_uci_move(&fen, "f6g8", None).unwrap().fen;
+        assert_eq!(move_counters(&after), (1, 4));
+    }
+
+    #[test]
+    fn expected_score_follows_the_elo_curve() {
+        assert!((crate::expected_score(1200, 1200) - 0.5).abs() < f32::EPSILON);
+        assert!((crate::expected_score(1600, 1200) - 0.909).abs() < 0.001);
+    }
+
+    #[test]
+    fn blunder_warning_flags_a_hung_queen() {
+        let fen = "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+        assert!(blunder_warning(fen, "d1h5").is_some());
+        assert!(blunder_warning(fen, "g1f3").is_none());
+    }
+
+    #[test]
+    fn principal_variation_is_legal_and_starts_with_the_best_move() {
+        assert_eq!(
+            principal_variation("k7/8/2K5/8/8/8/8/7R w - - 0 1", 3).len(),
+            3
+        );
+        assert_eq!(
+            principal_variation("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2)[0],
+            "a1a8"
+        );
+        let line = principal_variation(START, 4);
+        assert!(replay_fen(START, line.iter().map(String::as_str)).is_some());
+    }
+
+    #[test]
+    fn san_renders_castling() {
+        let white = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(san(&white, "e1g1"), "O-O");
+        assert_eq!(san(&white, "e1c1"), "O-O-O");
+        let black = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(san(&black, "e8g8"), "O-O");
+        assert_eq!(san(&black, "e8c8"), "O-O-O");
+    }
+
+    #[test]
+    fn san_marks_check_and_mate() {
+        let mate = apply_uci_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", None).unwrap();
+        assert_eq!(mate.san.as_deref(), Some("Ra8#"));
+        let check = apply_uci_move("6k1/5pp1/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", None).unwrap();
+        assert_eq!(check.san.as_deref(), Some("Ra8+"));
+    }
+
+    #[test]
+    fn san_disambiguates_by_file_or_rank() {
+        let knights = Board::from_str("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(san(&knights, "b1d2"), "Nbd2");
+        let rooks_on_rank = Board::from_str("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        assert_eq!(san(&rooks_on_rank, "a1d1"), "Rad1");
+        let rooks_on_file = Board::from_str("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(san(&rooks_on_file, "a1a2"), "R1a2");
+        let single = Board::from_str("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1").unwrap();
+        assert_eq!(san(&single, "g1f3"), "Nf3");
+    }
+
+    #[test]
+    fn san_renders_pawn_moves() {
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(san(&board, "e4d5"), "exd5");
+        assert_eq!(san(&board, "e4e5"), "e5");
+        let en_passant =
+            Board::from_str("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1")
+                .unwrap();
+        assert_eq!(san(&en_passant, "e5d6"), "exd6");
+        let promotion = Board::from_str("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(san(&promotion, "c7d8q"), "cxd8=Q+");
+        assert_eq!(san(&promotion, "c7c8n"), "c8=N");
+    }
+
+    #[test]
+    fn repetition_count_counts_returns_to_the_position() {
+        let moves = [
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ];
+        assert_eq!(repetition_count(START, moves), 3);
+        assert_eq!(repetition_count(START, moves[..4].iter().copied()), 2);
+    }
+
+    #[test]
+    fn insufficient_material_cases() {
+        assert!(insufficient_material("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient_material("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(insufficient_material("4k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        // Bishops on f1 and c8 both stand on light squares.
+        assert!(insufficient_material("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(!insufficient_material("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(!insufficient_material("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!insufficient_material("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        let outcome = apply_uci_move("4k3/8/8/8/8/8/3p4/4KB2 w - - 0 1", "e1d2", None).unwrap();
+        assert_eq!(
+            outcome.result,
+            Some(MatchResult::Draw(TerminationReason::InsufficientMaterial))
+        );
+    }
+
+    #[test]
+    fn book_answers_one_e4() {
+        let after_e4 = apply_uci_move(START, "e2e4", None).unwrap().fen;
+        assert_eq!(
+            pick_ai_move(&after_e4, 7, 1, Personality::Aggressive).unwrap(),
+            "e7e5"
+        );
+    }
+
+    #[test]
+    fn same_fen_and_seed_give_the_same_reply() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let first = pick_ai_move(fen, 11, 2, Personality::Balanced);
+        assert!(first.is_some());
+        for _ in 0..3 {
+            assert_eq!(pick_ai_move(fen, 11, 2, Personality::Balanced), first);
+        }
+    }
+
+    #[test]
+    fn mating_material_by_side() {
+        assert!(!has_mating_material(
+            "4k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            PlayerColor::White
+        ));
+        assert!(has_mating_material(
+            "4k3/8/8/8/8/8/8/4KBN1 w - - 0 1",
+            PlayerColor::White
+        ));
+        assert!(!has_mating_material(
+            "4k3/8/8/8/8/8/8/4KBN1 w - - 0 1",
+            PlayerColor::Black
+        ));
+        assert!(has_mating_material(
+            "4k3/8/8/8/8/8/P7/4K3 w - - 0 1",
+            PlayerColor::White
+        ));
+        assert!(has_mating_material(
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            PlayerColor::White
+        ));
+        assert!(!has_mating_material(
+            "4k3/8/8/8/8/8/8/3BKB2 w - - 0 1",
+            PlayerColor::White
+        ));
+    }
+
+    #[test]
+    fn elo_upsets_move_ratings_more() {
+        let upset = crate::elo_delta(1000, 1600, 1.0, 32.0);
+        let expected = crate::elo_delta(1600, 1000, 1.0, 32.0);
+        assert!(upset > expected);
+        assert_eq!(crate::elo_delta(1200, 1200, 0.5, 32.0), 0);
+        assert_eq!(crate::elo_delta(1200, 1200, 1.0, 32.0), 16);
+    }
+
+    #[test]
+    fn pgn_movetext_and_dates() {
+        assert_eq!(pgn_date(0), "1970.01.01");
+        assert_eq!(pgn_date(1_709_251_200_000_000), "2024.03.01");
+        assert_eq!(pgn_date(951_782_400_000_000), "2000.02.29");
+        let pgn = encode_pgn(&[("White", "a\"b".into())], START, ["e4", "e5", "Nf3"], "*");
+        assert!(pgn.contains("[White \"a\\\"b\"]"));
+        assert!(pgn.ends_with("1. e4 e5 2. Nf3 *\n"));
+        let black_first = encode_pgn(&[], "4k3/8/8/8/8/8/4P3/4K3 b - - 0 7", ["Kd7", "e4"], "*");
+        assert!(black_first.ends_with("7... Kd7 8. e4 *\n"));
+    }
+
+    #[test]
+    fn promotion_pieces_only_on_promoting_moves() {
+        assert!(apply_uci_move(START, "e2e4", Some("q")).is_err());
+        assert!(apply_uci_move("8/P7/8/8/8/8/8/k6K w - - 0 1", "a7a8", None).is_err());
+    }
+
+    #[test]
+    fn king_takes_rook_castles() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let short = apply_uci_move(fen, "e1h1", None).unwrap();
+        assert_eq!(short.uci, "e1g1");
+        assert_eq!(short.san.as_deref(), Some("O-O"));
+        assert_eq!(apply_uci_move(fen, "e1a1", None).unwrap().uci, "e1c1");
+        assert!(apply_uci_move(fen, "a1a8", None).is_ok());
+    }
+
+    #[test]
+    fn en_passant_target_round_trips_through_the_fen() {
+        let pushed = apply_uci_move(
+            "rnbqkbnr/ppp1pppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+            "e4e5",
+            None,
+        )
+        .unwrap();
+        let double_step = apply_uci_move(&pushed.fen, "f7f5", None).unwrap();
+        assert_eq!(
+            double_step.fen,
+            "rnbqkbnr/ppp1p1pp/8/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"
+        );
+        let capture = apply_uci_move(&double_step.fen, "e5f6", None).unwrap();
+        assert_eq!(
+            capture.fen,
+            "rnbqkbnr/ppp1p1pp/5P2/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3"
+        );
+        assert_eq!(capture.san.as_deref(), Some("exf6"));
+        // FENs stored before the target square was normalized still load.
+        let legacy = apply_uci_move(
+            "rnbqkbnr/ppp1p1pp/8/4Pp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f5 0 3",
+            "e5f6",
+            None,
+        )
+        .unwrap();
+        assert_eq!(legacy.fen, capture.fen);
+    }
+
+    #[test]
+    fn evaluate_fen_scores_from_whites_side() {
+        assert_eq!(evaluate_fen(START), Some(0));
+        assert!(evaluate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap() > 0);
+        assert!(evaluate_fen("3qk3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap() < 0);
+        assert_eq!(evaluate_fen("garbage"), None);
+    }
+
+    #[test]
+    fn in_check_covers_mate_and_a_mid_game_check() {
+        assert!(in_check("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1"));
+        assert!(in_check(
+            "rnbqkbnr/ppp1pppp/8/1B1p4/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 2"
+        ));
+        assert!(!in_check(START));
+    }
+}
@@ -2,22 +2,44 @@
 
 mod state;
 
-use std::str::FromStr;
-
 use chainchess::{
-    ChainChessAbi, ChainChessError, ChainChessResponse, GameStatus, MoveRecord, Operation,
-    PlayerColor, PlayerStats,
+    chess_engine::{
+        ai_accepts_draw, ai_move_seed, apply_uci_move, decode_share_code, has_mating_material,
+        legal_moves, mix_seed, move_counters, pick_ai_move, repetition_count, replay_fen,
+        side_to_move, validate_fen, EvalConfig, MatchResult, MoveComputation, AI_SEARCH_DEPTH,
+        DEFAULT_FEN,
+    },
+    elo_delta, AiMoveTrace, ChainChessAbi, ChainChessError, ChainChessParameters,
+    ChainChessResponse, GameEvent, GameOptions, GameStatus, MoveRecord, Operation, Personality,
+    PlayerColor, PlayerStats, Preferences, TerminationReason, TimeControl, TimeoutResult,
+    FIFTY_MOVE_PLIES, GAME_EVENTS_STREAM, INACTIVITY_ABORT_DAYS, MAX_AI_LEVEL, MAX_SCRIPTED_MOVES,
+    MAX_TRAINING_GAMES, MIN_AI_LEVEL, PROVISIONAL_GAMES, STARTING_RATING,
 };
-use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece, Square};
 use linera_sdk::{
-    linera_base_types::{ChainId, WithContractAbi},
+    linera_base_types::{ChainId, TimeDelta, Timestamp, WithContractAbi},
     views::{RootView, View},
     Contract, ContractRuntime,
 };
+use serde::{Deserialize, Serialize};
 use state::{ChainChessState, StoredGame};
 
-const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-const MAX_OPEN_GAMES_PER_CHAIN: usize = 64;
+/// Elo K-factor while a player's rating is provisional.
+const PROVISIONAL_K_FACTOR: f32 = 40.0;
+/// Elo K-factor once a player has `PROVISIONAL_GAMES` games behind them.
+const ESTABLISHED_K_FACTOR: f32 = 20.0;
+/// Rating the built-in AI counts as at the default `AI_SEARCH_DEPTH`, when a
+/// player's Elo change is computed.
+const AI_BASE_RATING: i32 = STARTING_RATING;
+/// Rating the AI gains for each search ply above the default, and loses for
+/// each one below, so a shallow AI is worth little to beat.
+const AI_RATING_PER_LEVEL: i32 = 200;
+/// Most half-moves the AI plays in a row, which only matters when it holds
+/// both seats.
+const MAX_AI_PLIES: usize = 16;
+/// Moves a stored game may hold before the oldest are archived.
+const MAX_STORED_MOVES: usize = 256;
+/// Moves left in a stored game after archiving.
+const KEPT_STORED_MOVES: usize = 64;
 
 pub struct ChainChessContract {
     state: ChainChessState,
@@ -31,10 +53,10 @@ impl WithContractAbi for ChainChessContract {
 }
 
 impl Contract for ChainChessContract {
-    type Message = ();
+    type Message = Message;
     type InstantiationArgument = ();
-    type Parameters = ();
-    type EventValue = ();
+    type Parameters = ChainChessParameters;
+    type EventValue = GameEvent;
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
         let state = ChainChessState::load(runtime.root_view_storage_context())
@@ -44,8 +66,11 @@ impl Contract for ChainChessContract {
     }
 
     async fn instantiate(&mut self, _argument: ()) {
-        // Ensure parameters are accessed to validate that they are empty.
-        self.runtime.application_parameters();
+        let parameters = self.runtime.application_parameters();
+        assert!(
+            parameters.max_open_games > 0,
+            "max_open_games must be at least 1"
+        );
         if *self.state.next_game_id.get() == 0 {
             self.state.next_game_id.set(1);
         }
@@ -56,7 +81,11 @@ impl Contract for ChainChessContract {
             Operation::CreateGame {
                 metadata,
                 play_vs_ai,
-            } => self.create_game(metadata, play_vs_ai).await,
+                options,
+            } => {
+                self.create_game(metadata, play_vs_ai, options.unwrap_or_default())
+                    .await
+            }
             Operation::JoinGame { game_id } => self.join_game(game_id).await,
             Operation::SubmitMove {
                 game_id,
@@ -64,6 +93,45 @@ impl Contract for ChainChessContract {
                 promotion,
             } => self.submit_move(game_id, uci, promotion).await,
             Operation::Resign { game_id } => self.resign(game_id).await,
+            Operation::ImportShareCode { code, metadata } => {
+                self.import_share_code(code, metadata).await
+            }
+            Operation::SeedTrainingGames { count } => self.seed_training_games(count).await,
+            Operation::AutoPlay { game_id } => self.auto_play(game_id).await,
+            Operation::ApproveJoin { game_id, accept } => self.approve_join(game_id, accept).await,
+            Operation::ForkForAnalysis { game_id, at_ply } => {
+                self.fork_for_analysis(game_id, at_ply).await
+            }
+            Operation::BlockPlayer { chain_id } => self.set_blocked(chain_id, true).await,
+            Operation::UnblockPlayer { chain_id } => self.set_blocked(chain_id, false).await,
+            Operation::PlayMoves { game_id, moves } => self.play_moves(game_id, moves).await,
+            Operation::MakeUnrated { game_id } => self.make_unrated(game_id).await,
+            Operation::ClaimDraw { game_id } => self.claim_draw(game_id).await,
+            Operation::OfferDraw { game_id } => self.offer_draw(game_id).await,
+            Operation::AcceptDraw { game_id } => self.answer_draw(game_id, true).await,
+            Operation::DeclineDraw { game_id } => self.answer_draw(game_id, false).await,
+            Operation::ClaimTimeout {
+                game_id,
+                result_preference,
+            } => {
+                self.claim_timeout(game_id, result_preference.unwrap_or_default())
+                    .await
+            }
+            Operation::AbortInactive { game_id } => self.abort_inactive(game_id).await,
+            Operation::CancelLobby { game_id } => self.cancel_lobby(game_id).await,
+            Operation::Rematch { game_id } => self.rematch(game_id).await,
+            Operation::RequestTakeback { game_id } => self.request_takeback(game_id).await,
+            Operation::AcceptTakeback { game_id } => self.accept_takeback(game_id).await,
+            Operation::SetPreferences {
+                default_time_control,
+                default_rated,
+            } => {
+                self.set_preferences(Preferences {
+                    default_time_control,
+                    default_rated,
+                })
+                .await
+            }
         };
 
         match result {
@@ -76,76 +144,385 @@ impl Contract for ChainChessContract {
         self.state.save().await.expect("Failed to save state");
     }
 
-    async fn execute_message(&mut self, _message: Self::Message) {
-        // This application currently does not use cross-chain messages.
+    async fn execute_message(&mut self, message: Self::Message) {
+        let Message::GameUpdate { game } = message;
+        let Some(origin) = self.runtime.message_origin_chain_id() else {
+            return;
+        };
+        // Only games this chain plays in are mirrored.
+        if game.color_of(self.runtime.chain_id()).is_none() {
+            return;
+        }
+        let key = (origin, game.game_id);
+        let newer = match self.state.remote_games.get(&key).await {
+            Ok(Some(mirrored)) => game.version > mirrored.version,
+            _ => true,
+        };
+        // Late or replayed updates never roll a mirrored game back. The
+        // version rises with every save, including takebacks that shorten
+        // the game.
+        if newer {
+            self.state
+                .remote_games
+                .insert(&key, game)
+                .expect("remote game insert should succeed");
+        }
     }
 }
 
+/// Cross-chain messages between the chains of a game's players.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// Latest state of a game hosted on the sending chain.
+    GameUpdate { game: StoredGame },
+}
+
 impl ChainChessContract {
     async fn create_game(
         &mut self,
         metadata: Option<String>,
         play_vs_ai: bool,
+        options: GameOptions,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        // Nobody ever joins an AI game, so settings for joiners are a mistake.
+        if play_vs_ai && (options.private_approval == Some(true) || options.min_rating.is_some()) {
+            return Err(ChainChessError::JoinSettingsOnAiGame);
+        }
+        let custom_start = options.start_fen.is_some();
+        let initial_fen = match options.start_fen {
+            Some(fen) => {
+                let fen = fen.trim().to_string();
+                validate_fen(&fen)?;
+                if legal_moves(&fen).is_empty() {
+                    return Err(ChainChessError::InvalidFen(
+                        "the position has no legal moves".into(),
+                    ));
+                }
+                fen
+            }
+            None => DEFAULT_FEN.to_string(),
+        };
+        let creator = self.runtime.chain_id();
+        self.ensure_lobby_capacity(creator).await?;
+        let game_id = self.allocate_game_id()?;
+        let now = self.runtime.system_time();
+        let mut game = Self::new_game(game_id, creator, initial_fen, now);
+        let preferences = self
+            .state
+            .player_preferences
+            .get(&creator)
+            .await
+            .unwrap_or_default()
+            .unwrap_or_default();
+        // A hand-picked position could be a won one, so it never moves ratings.
+        game.rated = preferences.default_rated && !custom_start;
+        let creator_color = options.creator_color.unwrap_or(PlayerColor::White);
+        if creator_color == PlayerColor::Black {
+            game.white = None;
+            game.black = Some(creator);
+        }
+        if play_vs_ai {
+            match creator_color {
+                PlayerColor::White => game.ai_black = true,
+                PlayerColor::Black => game.ai_white = true,
+            }
+        }
+        game.metadata = metadata;
+        game.private_approval = options.private_approval.unwrap_or(false);
+        game.min_rating = options.min_rating;
+        game.ai_personality = options.ai_personality.unwrap_or_default();
+        game.ai_level = options.ai_level.map_or(AI_SEARCH_DEPTH, |level| {
+            level.clamp(MIN_AI_LEVEL, MAX_AI_LEVEL)
+        });
+        if let Some(control) = options.time_control.or(preferences.default_time_control) {
+            Self::set_time_control(&mut game, control);
+        }
+        if play_vs_ai {
+            Self::start_game(&mut game, now);
+        }
+        self.emit_game_created(&game);
+        // The AI opens when it holds the side to move.
+        self.play_ai_reply(&mut game).await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(
+            "Game lobby created",
+            Some(game.to_summary()),
+        ))
+    }
+
+    async fn import_share_code(
+        &mut self,
+        code: String,
+        metadata: Option<String>,
     ) -> Result<ChainChessResponse, ChainChessError> {
+        let (initial_fen, moves) =
+            decode_share_code(&code).ok_or(ChainChessError::InvalidShareCode)?;
+        validate_fen(&initial_fen)?;
         let creator = self.runtime.chain_id();
-        let pending_games = self.list_games_for_chain(creator, false).await;
-        if pending_games >= MAX_OPEN_GAMES_PER_CHAIN {
-            return Err(ChainChessError::LobbyLimitReached);
+        self.ensure_lobby_capacity(creator).await?;
+
+        // Replay everything before allocating an id so a bad code leaves no trace.
+        let mut outcomes: Vec<MoveComputation> = Vec::with_capacity(moves.len());
+        for uci in moves {
+            let fen = outcomes.last().map_or(&initial_fen, |last| &last.fen);
+            if outcomes.last().is_some_and(|last| last.result.is_some()) {
+                return Err(ChainChessError::InvalidShareCode);
+            }
+            let outcome =
+                apply_uci_move(fen, &uci, None).map_err(|_| ChainChessError::InvalidShareCode)?;
+            outcomes.push(outcome);
         }
 
-        let game_id = *self.state.next_game_id.get();
-        self.state.next_game_id.set(game_id + 1);
         let now = self.runtime.system_time();
-        let game = StoredGame {
-            game_id,
-            white: creator,
-            black: None,
-            ai_black: play_vs_ai,
-            board_fen: DEFAULT_FEN.to_string(),
-            moves: Vec::new(),
-            turn: PlayerColor::White,
-            status: if play_vs_ai {
-                GameStatus::Active
-            } else {
-                GameStatus::Lobby
-            },
-            winner: None,
-            created_at: now,
-            updated_at: now,
-            metadata,
-        };
-        self.state
-            .active_games
-            .insert(&game_id, game.clone())
-            .expect("insert should not fail");
+        let mut game = Self::new_game(self.allocate_game_id()?, creator, initial_fen, now);
+        game.metadata = metadata;
+        // Imports come from any position with moves already made, so like forks
+        // they never move ratings.
+        game.rated = false;
+        self.emit_game_created(&game);
+        for outcome in outcomes {
+            let result = game.record_move(outcome, now, None);
+            self.emit_move_played(&game, result.is_some());
+            if let Some(result) = result {
+                self.finish_game(&mut game, result, now);
+            }
+        }
+        self.save_game(&mut game).await?;
         Ok(ChainChessResponse::ok(
-            "Game lobby created",
+            "Game imported",
             Some(game.to_summary()),
         ))
     }
 
+    async fn seed_training_games(
+        &mut self,
+        count: u8,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        if count == 0 || count > MAX_TRAINING_GAMES {
+            return Err(ChainChessError::InvalidTrainingCount);
+        }
+        let creator = self.runtime.chain_id();
+        // Training games have their own allowance, apart from real games.
+        let pending_games = self.list_games_for_chain(creator, false, true).await;
+        let limit = self.runtime.application_parameters().max_open_games;
+        if pending_games + usize::from(count) > limit {
+            return Err(ChainChessError::LobbyLimitReached(limit));
+        }
+        // Check the whole batch fits so a failure doesn't leave it half seeded.
+        (*self.state.next_game_id.get())
+            .max(1)
+            .checked_add(u64::from(count))
+            .ok_or(ChainChessError::GameIdExhausted)?;
+        let now = self.runtime.system_time();
+        let mut last = None;
+        for _ in 0..count {
+            let mut game = Self::new_game(
+                self.allocate_game_id()?,
+                creator,
+                DEFAULT_FEN.to_string(),
+                now,
+            );
+            // The AI holds both seats; the creator only watches and drives
+            // the game with `AutoPlay`.
+            game.white = None;
+            game.ai_white = true;
+            game.ai_black = true;
+            game.training = true;
+            Self::start_game(&mut game, now);
+            self.emit_game_created(&game);
+            self.save_game(&mut game).await?;
+            last = Some(game.to_summary());
+        }
+        Ok(ChainChessResponse::ok(
+            format!("Seeded {count} training games"),
+            last,
+        ))
+    }
+
+    async fn auto_play(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let mut game = self.load_game(game_id).await?;
+        if !game.training || game.creator != self.runtime.chain_id() {
+            return Err(ChainChessError::NotTrainingGame(game_id));
+        }
+        if game.status == GameStatus::Finished {
+            return Err(ChainChessError::AlreadyFinished);
+        }
+        let ai_move = self.play_ai_reply(&mut game).await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse {
+            ai_move,
+            ..ChainChessResponse::ok("Training game advanced", Some(game.to_summary()))
+        })
+    }
+
     async fn join_game(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
         let mut game = self.load_game(game_id).await?;
-        if game.ai_black {
-            return Err(ChainChessError::NotJoinable(game_id));
+        let caller = self.runtime.chain_id();
+        // Covers the creator and a chain that already joined, in either seat.
+        if game.color_of(caller).is_some() {
+            return Err(ChainChessError::CannotJoinOwnGame(game_id));
         }
-        if game.status != GameStatus::Lobby || game.black.is_some() {
+        if game.ai_white || game.ai_black {
             return Err(ChainChessError::NotJoinable(game_id));
         }
-        let caller = self.runtime.chain_id();
-        if caller == game.white {
+        let Some(open_seat) = Self::open_seat(&game) else {
+            return Err(ChainChessError::NotJoinable(game_id));
+        };
+        if game.status != GameStatus::Lobby || game.pending_joiner.is_some() {
             return Err(ChainChessError::NotJoinable(game_id));
         }
-        game.black = Some(caller);
-        game.status = GameStatus::Active;
+        if self.is_blocked(game.creator, caller).await {
+            return Err(ChainChessError::PlayerBlocked);
+        }
+        // A joined game takes one of the joiner's open-game slots too.
+        self.ensure_lobby_capacity(caller).await?;
+        if let Some(min_rating) = game.min_rating {
+            if self.state.rating_of(caller).await < min_rating {
+                return Err(ChainChessError::RatingTooLow(min_rating));
+            }
+        }
         game.updated_at = self.runtime.system_time();
-        self.save_game(&game)?;
+        if game.private_approval {
+            game.pending_joiner = Some(caller);
+            self.save_game(&mut game).await?;
+            return Ok(ChainChessResponse::ok(
+                "Join request sent to the host",
+                Some(game.to_summary()),
+            ));
+        }
+        Self::seat_player(&mut game, open_seat, caller);
+        Self::start_game(&mut game, self.runtime.system_time());
+        self.emit(GameEvent::GameJoined {
+            game_id,
+            player: caller,
+            color: open_seat,
+        });
+        self.save_game(&mut game).await?;
         Ok(ChainChessResponse::ok(
             "Joined game successfully",
             Some(game.to_summary()),
         ))
     }
 
+    async fn set_preferences(
+        &mut self,
+        preferences: Preferences,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        self.state
+            .player_preferences
+            .insert(&self.runtime.chain_id(), preferences)
+            .expect("preferences insert should succeed");
+        Ok(ChainChessResponse::ok("Preferences saved", None))
+    }
+
+    async fn set_blocked(
+        &mut self,
+        chain_id: ChainId,
+        blocked: bool,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let caller = self.runtime.chain_id();
+        if chain_id == caller {
+            return Err(ChainChessError::CannotBlockSelf);
+        }
+        let mut blocklist = self
+            .state
+            .blocklist
+            .get(&caller)
+            .await
+            .unwrap_or_default()
+            .unwrap_or_default();
+        blocklist.retain(|entry| *entry != chain_id);
+        if blocked {
+            blocklist.push(chain_id);
+        }
+        self.state
+            .blocklist
+            .insert(&caller, blocklist)
+            .expect("blocklist insert should succeed");
+        let message = if blocked {
+            "Player blocked"
+        } else {
+            "Player unblocked"
+        };
+        Ok(ChainChessResponse::ok(message, None))
+    }
+
+    /// Whether either chain has blocked the other.
+    async fn is_blocked(&self, a: ChainId, b: ChainId) -> bool {
+        for (blocker, blocked) in [(a, b), (b, a)] {
+            if let Ok(Some(list)) = self.state.blocklist.get(&blocker).await {
+                if list.contains(&blocked) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    async fn approve_join(
+        &mut self,
+        game_id: u64,
+        accept: bool,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let mut game = self.load_game(game_id).await?;
+        if self.runtime.chain_id() != game.creator {
+            return Err(ChainChessError::NotHost);
+        }
+        if game.status != GameStatus::Lobby {
+            return Err(ChainChessError::NotJoinable(game_id));
+        }
+        let joiner = game
+            .pending_joiner
+            .take()
+            .ok_or(ChainChessError::NoPendingJoin(game_id))?;
+        game.updated_at = self.runtime.system_time();
+        let message = if accept {
+            let Some(open_seat) = Self::open_seat(&game) else {
+                return Err(ChainChessError::NotJoinable(game_id));
+            };
+            Self::seat_player(&mut game, open_seat, joiner);
+            Self::start_game(&mut game, self.runtime.system_time());
+            self.emit(GameEvent::GameJoined {
+                game_id,
+                player: joiner,
+                color: open_seat,
+            });
+            "Join request approved"
+        } else {
+            self.emit(GameEvent::JoinRejected {
+                game_id,
+                player: joiner,
+            });
+            "Join request rejected"
+        };
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
+    }
+
+    async fn cancel_lobby(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let game = self.load_game(game_id).await?;
+        if self.runtime.chain_id() != game.creator {
+            return Err(ChainChessError::NotHost);
+        }
+        match game.status {
+            GameStatus::Lobby => {}
+            GameStatus::Active => return Err(ChainChessError::AlreadyStarted(game_id)),
+            GameStatus::Finished => return Err(ChainChessError::AlreadyFinished),
+        }
+        self.state
+            .active_games
+            .remove(&game_id)
+            .map_err(|_| ChainChessError::GameNotFound(game_id))?;
+        if game.archived_moves > 0 {
+            self.state
+                .move_history_overflow
+                .remove(&game_id)
+                .map_err(|_| ChainChessError::GameNotFound(game_id))?;
+        }
+        self.emit(GameEvent::LobbyCancelled { game_id });
+        Ok(ChainChessResponse::ok("Lobby cancelled", None))
+    }
+
     async fn submit_move(
         &mut self,
         game_id: u64,
@@ -160,64 +537,202 @@ impl ChainChessContract {
             return Err(ChainChessError::MissingOpponent);
         }
 
-        let caller = self.runtime.chain_id();
-        let player_color = if caller == game.white {
-            PlayerColor::White
-        } else if game.black == Some(caller) {
-            PlayerColor::Black
-        } else if game.ai_black && game.black.is_none() && caller == game.white {
-            PlayerColor::White
-        } else {
+        if game.training {
             return Err(ChainChessError::NotParticipant);
-        };
+        }
+
+        let caller = self.runtime.chain_id();
+        let player_color = game
+            .color_of(caller)
+            .ok_or(ChainChessError::NotParticipant)?;
 
         if player_color != game.turn {
             return Err(ChainChessError::NotYourTurn);
         }
 
-        let move_outcome = Self::apply_uci_move(&game.board_fen, &uci, promotion.as_deref())
-            .map_err(|_| {
-                ChainChessError::InvalidMove("move is illegal in current position".into())
-            })?;
-
+        // A move after the flag has fallen would credit the increment to an
+        // empty clock; the game is the opponent's to claim instead.
+        let clock = match player_color {
+            PlayerColor::White => game.white_time_ms,
+            PlayerColor::Black => game.black_time_ms,
+        };
         let now = self.runtime.system_time();
-        game.board_fen = move_outcome.fen;
-        game.turn = player_color.other();
-        game.moves.push(MoveRecord {
-            uci: move_outcome.uci,
-            san: move_outcome.san,
-            played_by: player_color,
-            played_at: now,
-        });
-        game.updated_at = now;
+        if let (Some(started), Some(remaining)) = (game.last_move_at, clock) {
+            if now.delta_since(started).as_micros() / 1_000 >= remaining {
+                return Err(ChainChessError::OutOfTime);
+            }
+        }
 
-        if let Some(result) = move_outcome.result {
+        let move_outcome = apply_uci_move(&game.board_fen, &uci, promotion.as_deref())?;
+        // Checkmate and stalemate on the board end the game first; draw rules
+        // and draw agreements are only considered while it is still active.
+        let result = game.record_move(move_outcome, now, None);
+        self.emit_move_played(&game, result.is_some());
+        if let Some(result) = result {
             self.apply_result(&mut game, result).await?;
         }
 
-        if game.ai_black && game.status == GameStatus::Active && game.turn == PlayerColor::Black {
-            if let Some(ai_move) = Self::pick_ai_move(&game.board_fen) {
-                if let Ok(ai_outcome) = Self::apply_uci_move(&game.board_fen, &ai_move, None) {
-                    let ai_time = self.runtime.system_time();
-                    game.board_fen = ai_outcome.fen;
-                    game.turn = PlayerColor::White;
-                    game.moves.push(MoveRecord {
-                        uci: ai_outcome.uci,
-                        san: ai_outcome.san,
-                        played_by: PlayerColor::Black,
-                        played_at: ai_time,
-                    });
-                    game.updated_at = ai_time;
-                    if let Some(result) = ai_outcome.result {
-                        self.apply_result(&mut game, result).await?;
-                    }
+        let ai_move = self.play_ai_reply(&mut game).await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse {
+            ai_move,
+            ..ChainChessResponse::ok("Move accepted", Some(game.to_summary()))
+        })
+    }
+
+    /// Lets the AI move for as long as it holds the side to move, up to
+    /// `MAX_AI_PLIES` half-moves, returning the last move it played. In
+    /// training games it holds both seats and plays for each side in turn.
+    async fn play_ai_reply(
+        &mut self,
+        game: &mut StoredGame,
+    ) -> Result<Option<MoveRecord>, ChainChessError> {
+        let mut last_move = None;
+        for _ in 0..MAX_AI_PLIES {
+            if game.status != GameStatus::Active || !game.is_ai(game.turn) {
+                break;
+            }
+            let plies = Self::ply_count(game);
+            if !self.play_ai_move(game).await? {
+                break;
+            }
+            if Self::ply_count(game) > plies {
+                last_move = game.moves.last().cloned();
+            }
+        }
+        Ok(last_move)
+    }
+
+    /// Plays one AI move, or agrees a draw in a dead position. Returns
+    /// whether the game changed.
+    async fn play_ai_move(&mut self, game: &mut StoredGame) -> Result<bool, ChainChessError> {
+        // In long, dead-equal games the AI agrees a draw instead of replying.
+        if ai_accepts_draw(&game.board_fen, Self::ply_count(game), &EvalConfig::DEFAULT) {
+            self.apply_result(game, MatchResult::Draw(TerminationReason::Agreement))
+                .await?;
+            return Ok(true);
+        }
+
+        let trace = AiMoveTrace {
+            seed: ai_move_seed(game.ai_seed, Self::ply_count(game)),
+            depth: game.ai_level,
+            personality: game.ai_personality,
+        };
+        if let Some(ai_move) =
+            pick_ai_move(&game.board_fen, trace.seed, trace.depth, trace.personality)
+        {
+            if let Ok(ai_outcome) = apply_uci_move(&game.board_fen, &ai_move, None) {
+                let now = self.runtime.system_time();
+                let result = game.record_move(ai_outcome, now, Some(trace));
+                self.emit_move_played(game, result.is_some());
+                if let Some(result) = result {
+                    self.apply_result(game, result).await?;
                 }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn make_unrated(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        // The AI could not object, so a game turning against its human could
+        // always be dropped from the ratings.
+        if game.is_ai(color.other()) {
+            return Err(ChainChessError::AiGameStaysRated);
+        }
+        let opponent_confirmed = game.unrated_request == Some(color.other());
+        let message = if opponent_confirmed {
+            game.rated = false;
+            game.unrated_request = None;
+            self.emit(GameEvent::GameUnrated { game_id });
+            "Game is now unrated"
+        } else {
+            game.unrated_request = Some(color);
+            "Waiting for the opponent to agree to an unrated game"
+        };
+        game.updated_at = self.runtime.system_time();
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
+    }
+
+    async fn play_moves(
+        &mut self,
+        game_id: u64,
+        moves: Vec<String>,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        if moves.len() > MAX_SCRIPTED_MOVES {
+            return Err(ChainChessError::TooManyMoves);
+        }
+        let mut game = self.load_game(game_id).await?;
+        if game.status == GameStatus::Finished {
+            return Err(ChainChessError::AlreadyFinished);
+        }
+        // Scripted moves must never decide a rated game.
+        let scriptable = game.training || (game.status == GameStatus::Lobby && !game.rated);
+        if self.runtime.chain_id() != game.creator || !scriptable {
+            return Err(ChainChessError::NotScriptable);
+        }
+
+        // Apply everything to a scratch position first so an illegal move
+        // leaves the stored game untouched.
+        let mut outcomes: Vec<MoveComputation> = Vec::with_capacity(moves.len());
+        for uci in &moves {
+            if outcomes.last().is_some_and(|last| last.result.is_some()) {
+                return Err(ChainChessError::AlreadyFinished);
+            }
+            let fen = outcomes.last().map_or(&game.board_fen, |last| &last.fen);
+            outcomes.push(apply_uci_move(fen, uci, None)?);
+        }
+
+        let now = self.runtime.system_time();
+        for outcome in outcomes {
+            let result = game.record_move(outcome, now, None);
+            self.emit_move_played(&game, result.is_some());
+            if let Some(result) = result {
+                // Scripted games never count towards the leaderboard.
+                self.finish_game(&mut game, result, now);
             }
         }
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(
+            format!("Played {} moves", moves.len()),
+            Some(game.to_summary()),
+        ))
+    }
+
+    async fn fork_for_analysis(
+        &mut self,
+        game_id: u64,
+        at_ply: usize,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let source = self.load_game(game_id).await?;
+        if source.status != GameStatus::Finished {
+            return Err(ChainChessError::NotFinished(game_id));
+        }
+        let history = self
+            .state
+            .full_history(source.game_id, source.archived_moves, &source.moves)
+            .await;
+        let fen = source
+            .board_at_ply(&history, at_ply)
+            .filter(|fen| !legal_moves(fen).is_empty())
+            .ok_or(ChainChessError::InvalidPly(at_ply))?;
+        let creator = self.runtime.chain_id();
+        self.ensure_lobby_capacity(creator).await?;
 
-        self.save_game(&game)?;
+        let now = self.runtime.system_time();
+        let mut game = Self::new_game(self.allocate_game_id()?, creator, fen, now);
+        game.ai_black = true;
+        Self::start_game(&mut game, now);
+        // Forks start from a chosen position, so they never move ratings.
+        game.rated = false;
+        game.metadata = Some(format!("Analysis of game {game_id} from ply {at_ply}"));
+        self.emit_game_created(&game);
+        self.play_ai_reply(&mut game).await?;
+        self.save_game(&mut game).await?;
         Ok(ChainChessResponse::ok(
-            "Move accepted",
+            "Analysis game created",
             Some(game.to_summary()),
         ))
     }
@@ -229,85 +744,592 @@ impl ChainChessContract {
         }
 
         let caller = self.runtime.chain_id();
-        let player_color = if caller == game.white {
-            PlayerColor::White
-        } else if game.black == Some(caller) {
-            PlayerColor::Black
-        } else {
-            return Err(ChainChessError::NotParticipant);
-        };
+        let player_color = game
+            .color_of(caller)
+            .ok_or(ChainChessError::NotParticipant)?;
 
-        self.apply_result(&mut game, MatchResult::Winner(player_color.other()))
-            .await?;
-        self.save_game(&game)?;
+        self.apply_result(
+            &mut game,
+            MatchResult::Winner(player_color.other(), TerminationReason::Resignation),
+        )
+        .await?;
+        self.save_game(&mut game).await?;
         Ok(ChainChessResponse::ok(
             "Resigned successfully",
             Some(game.to_summary()),
         ))
     }
 
-    async fn load_game(&self, game_id: u64) -> Result<StoredGame, ChainChessError> {
-        self.state
-            .active_games
-            .get(&game_id)
-            .await
-            .map_err(|_| ChainChessError::GameNotFound(game_id))?
-            .ok_or(ChainChessError::GameNotFound(game_id))
+    async fn claim_draw(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, _) = self.load_active_seat(game_id).await?;
+        let (reason, message) = if game.halfmove_clock >= FIFTY_MOVE_PLIES {
+            (TerminationReason::FiftyMove, "Draw by the fifty-move rule")
+        } else {
+            let history = self
+                .state
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await;
+            let repetitions = repetition_count(
+                &game.initial_fen,
+                history.iter().map(|record| record.uci.as_str()),
+            );
+            if repetitions < 3 {
+                return Err(ChainChessError::DrawClaimRejected);
+            }
+            (TerminationReason::Threefold, "Draw by threefold repetition")
+        };
+        self.apply_result(&mut game, MatchResult::Draw(reason))
+            .await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
     }
 
-    fn save_game(&mut self, game: &StoredGame) -> Result<(), ChainChessError> {
-        self.state
-            .active_games
-            .insert(&game.game_id, game.clone())
-            .map_err(|_| ChainChessError::GameNotFound(game.game_id))
+    async fn claim_timeout(
+        &mut self,
+        game_id: u64,
+        preference: TimeoutResult,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        let clock = match game.turn {
+            PlayerColor::White => game.white_time_ms,
+            PlayerColor::Black => game.black_time_ms,
+        };
+        let (Some(started), Some(remaining)) = (game.last_move_at, clock) else {
+            return Err(ChainChessError::Untimed(game_id));
+        };
+        let elapsed_ms = self.runtime.system_time().delta_since(started).as_micros() / 1_000;
+        if game.turn == color || remaining > elapsed_ms {
+            return Err(ChainChessError::ClockNotExpired);
+        }
+        // Running out of time only loses if the claimant could still mate.
+        let (result, message) = if preference == TimeoutResult::Draw {
+            (
+                MatchResult::Draw(TerminationReason::Timeout),
+                "Game drawn on the opponent's flag",
+            )
+        } else if has_mating_material(&game.board_fen, color) {
+            (
+                MatchResult::Winner(color, TerminationReason::Timeout),
+                "Won on time",
+            )
+        } else {
+            (
+                MatchResult::Draw(TerminationReason::Timeout),
+                "Flag fell, but drawn for lack of mating material",
+            )
+        };
+        self.apply_result(&mut game, result).await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
     }
 
-    async fn apply_result(
+    async fn abort_inactive(
         &mut self,
-        game: &mut StoredGame,
-        result: MatchResult,
-    ) -> Result<(), ChainChessError> {
-        game.status = GameStatus::Finished;
-        game.winner = match result {
-            MatchResult::Winner(color) => Some(color),
-            MatchResult::Draw => None,
+        game_id: u64,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        // Only the player left waiting may abort; the stalled side has to move.
+        if game.turn == color {
+            return Err(ChainChessError::NotAbortableOnYourMove);
+        }
+        // Draw offers and other requests touch `updated_at`, so idleness is
+        // counted from the start of the current turn instead.
+        let turn_started = game
+            .last_move_at
+            .or_else(|| game.moves.last().map(|record| record.played_at))
+            .unwrap_or(game.created_at);
+        let idle = self.runtime.system_time().delta_since(turn_started);
+        if idle < TimeDelta::from_secs(INACTIVITY_ABORT_DAYS * 24 * 60 * 60) {
+            return Err(ChainChessError::NotYetAbortable);
+        }
+        self.apply_result(
+            &mut game,
+            MatchResult::Winner(color, TerminationReason::Abandoned),
+        )
+        .await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(
+            "Inactive game awarded to the player present",
+            Some(game.to_summary()),
+        ))
+    }
+
+    async fn offer_draw(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        game.updated_at = self.runtime.system_time();
+        let message = if self.player_chain(&game, color.other()).is_some() {
+            game.draw_offer = Some(color);
+            "Draw offered"
+        } else if ai_accepts_draw(
+            &game.board_fen,
+            Self::ply_count(&game),
+            &EvalConfig::DEFAULT,
+        ) {
+            self.apply_result(&mut game, MatchResult::Draw(TerminationReason::Agreement))
+                .await?;
+            "The AI accepted the draw"
+        } else {
+            "The AI declined the draw"
         };
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
+    }
+
+    async fn answer_draw(
+        &mut self,
+        game_id: u64,
+        accept: bool,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        if game.draw_offer != Some(color.other()) {
+            return Err(ChainChessError::NoDrawOffer);
+        }
+        game.draw_offer = None;
         game.updated_at = self.runtime.system_time();
+        let message = if accept {
+            self.apply_result(&mut game, MatchResult::Draw(TerminationReason::Agreement))
+                .await?;
+            "Draw agreed"
+        } else {
+            "Draw offer declined"
+        };
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
+    }
 
-        if let Some(winner) = game.winner {
-            if let Some(winner_chain) = self.player_chain(game, winner) {
-                self.bump_stats(winner_chain, |stats| {
-                    stats.wins += 1;
-                    stats.games_played += 1;
-                    stats.rating += 10;
-                })
-                .await;
-            }
-            if let Some(loser_chain) = self.player_chain(game, winner.other()) {
-                self.bump_stats(loser_chain, |stats| {
-                    stats.losses += 1;
-                    stats.games_played += 1;
-                    stats.rating -= 5;
-                })
-                .await;
-            }
+    async fn request_takeback(
+        &mut self,
+        game_id: u64,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        Self::takeback_plies(&game, color).ok_or(ChainChessError::NoMoveToTakeBack)?;
+        let message = if self.player_chain(&game, color.other()).is_some() {
+            game.takeback_request = Some(color);
+            game.updated_at = self.runtime.system_time();
+            "Takeback requested"
         } else {
-            for color in [PlayerColor::White, PlayerColor::Black] {
-                if let Some(chain) = self.player_chain(game, color) {
-                    self.bump_stats(chain, |stats| {
-                        stats.draws += 1;
-                        stats.games_played += 1;
-                        stats.rating += 1;
-                    })
-                    .await;
-                }
+            self.take_back(&mut game, color).await?;
+            // Free undos against a fixed-strength opponent would farm rating.
+            if game.rated {
+                game.rated = false;
+                self.emit(GameEvent::GameUnrated { game_id });
+                "The AI allowed the takeback; the game is now unrated"
+            } else {
+                "The AI allowed the takeback"
             }
-        }
-
-        Ok(())
+        };
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(message, Some(game.to_summary())))
     }
 
-    async fn bump_stats<F>(&mut self, chain_id: ChainId, mut f: F)
+    async fn accept_takeback(
+        &mut self,
+        game_id: u64,
+    ) -> Result<ChainChessResponse, ChainChessError> {
+        let (mut game, color) = self.load_active_seat(game_id).await?;
+        if game.takeback_request != Some(color.other()) {
+            return Err(ChainChessError::NoTakebackRequest);
+        }
+        self.take_back(&mut game, color.other()).await?;
+        self.save_game(&mut game).await?;
+        Ok(ChainChessResponse::ok(
+            "Move taken back",
+            Some(game.to_summary()),
+        ))
+    }
+
+    /// Half-moves to undo so that `requester` is to move again before their
+    /// last move: one if the opponent has not replied yet, otherwise two.
+    /// `None` when `requester` has no move among the stored ones.
+    fn takeback_plies(game: &StoredGame, requester: PlayerColor) -> Option<usize> {
+        let plies = if game.turn == requester { 2 } else { 1 };
+        let index = game.moves.len().checked_sub(plies)?;
+        (game.moves[index].played_by == requester).then_some(plies)
+    }
+
+    /// Undoes `requester`'s last move (and any reply to it) and rebuilds the
+    /// board by replaying the remaining moves from the initial position.
+    async fn take_back(
+        &mut self,
+        game: &mut StoredGame,
+        requester: PlayerColor,
+    ) -> Result<(), ChainChessError> {
+        let plies =
+            Self::takeback_plies(game, requester).ok_or(ChainChessError::NoMoveToTakeBack)?;
+        let kept = game.moves.len() - plies;
+        let history = self
+            .state
+            .full_history(game.game_id, game.archived_moves, &game.moves[..kept])
+            .await;
+        let fen = replay_fen(
+            &game.initial_fen,
+            history.iter().map(|record| record.uci.as_str()),
+        )
+        .ok_or_else(|| {
+            ChainChessError::InconsistentGame(
+                game.game_id,
+                "the earlier moves do not replay".into(),
+            )
+        })?;
+
+        let now = self.runtime.system_time();
+        game.moves.truncate(kept);
+        game.halfmove_clock = move_counters(&fen).0;
+        game.board_fen = fen;
+        game.turn = requester;
+        game.takeback_request = None;
+        game.draw_offer = None;
+        if game.last_move_at.is_some() {
+            game.last_move_at = Some(now);
+        }
+        game.updated_at = now;
+        self.emit(GameEvent::MovesTakenBack {
+            game_id: game.game_id,
+            plies,
+        });
+        Ok(())
+    }
+
+    async fn rematch(&mut self, game_id: u64) -> Result<ChainChessResponse, ChainChessError> {
+        let mut game = self.load_game(game_id).await?;
+        let caller = self.runtime.chain_id();
+        let color = game
+            .color_of(caller)
+            .ok_or(ChainChessError::NotParticipant)?;
+        if game.status != GameStatus::Finished {
+            return Err(ChainChessError::NotFinished(game_id));
+        }
+        if let Some(rematch_id) = game.rematch_id {
+            return Err(ChainChessError::AlreadyRematched(rematch_id));
+        }
+        let opponent = self
+            .player_chain(&game, color.other())
+            .ok_or(ChainChessError::NoRematchOpponent)?;
+        if game.rematch_request != Some(color.other()) {
+            game.rematch_request = Some(color);
+            self.save_game(&mut game).await?;
+            return Ok(ChainChessResponse::ok(
+                "Waiting for the opponent to accept the rematch",
+                Some(game.to_summary()),
+            ));
+        }
+
+        // The rematch takes an open-game slot from both players.
+        self.ensure_lobby_capacity(caller).await?;
+        self.ensure_lobby_capacity(opponent).await?;
+        let rematch_id = self.allocate_game_id()?;
+        game.rematch_request = None;
+        game.rematch_id = Some(rematch_id);
+        self.save_game(&mut game).await?;
+        let now = self.runtime.system_time();
+        let mut rematch = Self::new_game(rematch_id, caller, game.initial_fen.clone(), now);
+        Self::seat_player(&mut rematch, color, opponent);
+        Self::seat_player(&mut rematch, color.other(), caller);
+        // The rematch is played on the same terms as the original.
+        rematch.rated = game.rated;
+        if let Some(control) = game.time_control {
+            Self::set_time_control(&mut rematch, control);
+        }
+        Self::start_game(&mut rematch, now);
+        rematch.metadata = game.metadata.clone();
+        rematch.rematch_of = Some(game_id);
+        self.emit_game_created(&rematch);
+        self.save_game(&mut rematch).await?;
+        Ok(ChainChessResponse::ok(
+            "Rematch started",
+            Some(rematch.to_summary()),
+        ))
+    }
+
+    /// Loads an active game and the caller's seat in it.
+    async fn load_active_seat(
+        &mut self,
+        game_id: u64,
+    ) -> Result<(StoredGame, PlayerColor), ChainChessError> {
+        let game = self.load_game(game_id).await?;
+        match game.status {
+            GameStatus::Finished => return Err(ChainChessError::AlreadyFinished),
+            GameStatus::Lobby => return Err(ChainChessError::MissingOpponent),
+            GameStatus::Active => {}
+        }
+        let color = game
+            .color_of(self.runtime.chain_id())
+            .ok_or(ChainChessError::NotParticipant)?;
+        Ok((game, color))
+    }
+
+    /// A fresh lobby created by `creator`, seated as White, starting from
+    /// `initial_fen`. The side to move is taken from the FEN.
+    fn new_game(
+        game_id: u64,
+        creator: ChainId,
+        initial_fen: String,
+        created_at: Timestamp,
+    ) -> StoredGame {
+        StoredGame {
+            game_id,
+            version: 0,
+            creator,
+            white: Some(creator),
+            black: None,
+            ai_black: false,
+            ai_white: false,
+            training: false,
+            ai_seed: mix_seed(game_id ^ created_at.micros()),
+            turn: side_to_move(&initial_fen).unwrap_or(PlayerColor::White),
+            halfmove_clock: move_counters(&initial_fen).0,
+            board_fen: initial_fen.clone(),
+            initial_fen,
+            moves: Vec::new(),
+            archived_moves: 0,
+            archived_captures: 0,
+            status: GameStatus::Lobby,
+            winner: None,
+            termination: None,
+            created_at,
+            updated_at: created_at,
+            metadata: None,
+            private_approval: false,
+            pending_joiner: None,
+            min_rating: None,
+            ai_personality: Personality::Balanced,
+            ai_level: AI_SEARCH_DEPTH,
+            rated: true,
+            unrated_request: None,
+            draw_offer: None,
+            rematch_request: None,
+            takeback_request: None,
+            rematch_of: None,
+            rematch_id: None,
+            white_time_ms: None,
+            black_time_ms: None,
+            increment_ms: 0,
+            time_control: None,
+            last_move_at: None,
+        }
+    }
+
+    /// Half-moves played in `game`, including archived ones.
+    fn ply_count(game: &StoredGame) -> usize {
+        game.archived_moves as usize + game.moves.len()
+    }
+
+    async fn ensure_lobby_capacity(&mut self, chain: ChainId) -> Result<(), ChainChessError> {
+        let pending_games = self.list_games_for_chain(chain, false, false).await;
+        let limit = self.runtime.application_parameters().max_open_games;
+        if pending_games >= limit {
+            return Err(ChainChessError::LobbyLimitReached(limit));
+        }
+        Ok(())
+    }
+
+    fn allocate_game_id(&mut self) -> Result<u64, ChainChessError> {
+        // Game ids start at 1; repair a zeroed counter rather than handing out id 0.
+        let game_id = (*self.state.next_game_id.get()).max(1);
+        let next = game_id
+            .checked_add(1)
+            .ok_or(ChainChessError::GameIdExhausted)?;
+        self.state.next_game_id.set(next);
+        Ok(game_id)
+    }
+
+    async fn load_game(&mut self, game_id: u64) -> Result<StoredGame, ChainChessError> {
+        let game = self
+            .state
+            .active_games
+            .get(&game_id)
+            .await
+            .map_err(|_| ChainChessError::GameNotFound(game_id))?
+            .ok_or(ChainChessError::GameNotFound(game_id))?;
+        if self.runtime.application_parameters().verify_on_load {
+            let history = self
+                .state
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await;
+            game.verify(&history)?;
+        }
+        Ok(game)
+    }
+
+    /// Stores `game`, first archiving its oldest moves once it holds more
+    /// than `MAX_STORED_MOVES`.
+    async fn save_game(&mut self, game: &mut StoredGame) -> Result<(), ChainChessError> {
+        game.version += 1;
+        if game.moves.len() > MAX_STORED_MOVES {
+            let spilled = game.moves.len() - KEPT_STORED_MOVES;
+            let mut archive = self
+                .state
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await;
+            archive.truncate(Self::ply_count(game) - KEPT_STORED_MOVES);
+            let spilled_captures = game.moves[..spilled]
+                .iter()
+                .filter(|record| record.captured.is_some())
+                .count();
+            game.moves.drain(..spilled);
+            game.archived_moves += spilled as u32;
+            game.archived_captures += spilled_captures as u32;
+            self.state
+                .move_history_overflow
+                .insert(&game.game_id, archive)
+                .map_err(|_| ChainChessError::GameNotFound(game.game_id))?;
+        }
+        self.state
+            .active_games
+            .insert(&game.game_id, game.clone())
+            .map_err(|_| ChainChessError::GameNotFound(game.game_id))?;
+        self.notify_players(game);
+        Ok(())
+    }
+
+    /// Sends the game to the chains of its players other than this one, once
+    /// it has started.
+    fn notify_players(&mut self, game: &StoredGame) {
+        if game.status == GameStatus::Lobby {
+            return;
+        }
+        let here = self.runtime.chain_id();
+        for color in [PlayerColor::White, PlayerColor::Black] {
+            if let Some(chain) = game.seat(color).filter(|chain| *chain != here) {
+                self.runtime
+                    .send_message(chain, Message::GameUpdate { game: game.clone() });
+            }
+        }
+    }
+
+    async fn apply_result(
+        &mut self,
+        game: &mut StoredGame,
+        result: MatchResult,
+    ) -> Result<(), ChainChessError> {
+        let now = self.runtime.system_time();
+        self.finish_game(game, result, now);
+        if game.training {
+            return Ok(());
+        }
+        let rated = game.rated;
+
+        // Both changes are computed from the ratings before the game.
+        let mut ratings = [Self::ai_rating(game.ai_level); 2];
+        let mut k_factors = [ESTABLISHED_K_FACTOR; 2];
+        for color in [PlayerColor::White, PlayerColor::Black] {
+            if let Some(chain) = self.player_chain(game, color) {
+                let stats = self
+                    .state
+                    .leaderboard
+                    .get(&chain)
+                    .await
+                    .unwrap_or_default()
+                    .unwrap_or_else(|| PlayerStats::new(chain));
+                ratings[color as usize] = stats.rating;
+                if stats.games_played < PROVISIONAL_GAMES {
+                    k_factors[color as usize] = PROVISIONAL_K_FACTOR;
+                }
+            }
+        }
+        for color in [PlayerColor::White, PlayerColor::Black] {
+            let Some(chain) = self.player_chain(game, color) else {
+                continue;
+            };
+            let score = match game.winner {
+                Some(winner) if winner == color => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            let delta = elo_delta(
+                ratings[color as usize],
+                ratings[color.other() as usize],
+                score,
+                k_factors[color as usize],
+            );
+            self.bump_stats(chain, |stats| {
+                stats.record_outcome(color, game.winner);
+                if rated {
+                    stats.rating += delta;
+                }
+            })
+            .await;
+        }
+
+        if let (Some(white), Some(black)) = (game.white, game.black) {
+            self.record_head_to_head(white, black, game.winner).await;
+        }
+        Ok(())
+    }
+
+    /// Adds a finished game between `white` and `black` to their record.
+    async fn record_head_to_head(
+        &mut self,
+        white: ChainId,
+        black: ChainId,
+        winner: Option<PlayerColor>,
+    ) {
+        let (key, first_color) = if white <= black {
+            ((white, black), PlayerColor::White)
+        } else {
+            ((black, white), PlayerColor::Black)
+        };
+        let mut record = self
+            .state
+            .head_to_head_records
+            .get(&key)
+            .await
+            .unwrap_or_default()
+            .unwrap_or_default();
+        match winner {
+            Some(color) if color == first_color => record.wins += 1,
+            Some(_) => record.losses += 1,
+            None => record.draws += 1,
+        }
+        self.state
+            .head_to_head_records
+            .insert(&key, record)
+            .expect("head-to-head insert should succeed");
+    }
+
+    fn emit(&mut self, event: GameEvent) {
+        self.runtime.emit(GAME_EVENTS_STREAM.into(), &event);
+    }
+
+    /// Emits `GameCreated` for a game that has just been set up.
+    fn emit_game_created(&mut self, game: &StoredGame) {
+        self.emit(GameEvent::GameCreated {
+            game_id: game.game_id,
+            creator: game.creator,
+            status: game.status,
+        });
+    }
+
+    /// Ends `game` with `result` and emits `GameFinished`. Ratings are left
+    /// to `apply_result`.
+    fn finish_game(&mut self, game: &mut StoredGame, result: MatchResult, at: Timestamp) {
+        game.finish(result, at);
+        self.emit(GameEvent::GameFinished {
+            game_id: game.game_id,
+            winner: game.winner,
+            reason: result.reason(),
+        });
+    }
+
+    /// Emits `MovePlayed` for the move `game` has just recorded.
+    fn emit_move_played(&mut self, game: &StoredGame, ends_game: bool) {
+        let Some(record) = game.moves.last() else {
+            return;
+        };
+        let status = if ends_game {
+            GameStatus::Finished
+        } else {
+            game.status
+        };
+        self.emit(GameEvent::MovePlayed {
+            game_id: game.game_id,
+            uci: record.uci.clone(),
+            played_by: record.played_by,
+            status,
+        });
+    }
+
+    async fn bump_stats<F>(&mut self, chain_id: ChainId, mut f: F)
     where
         F: FnMut(&mut PlayerStats),
     {
@@ -319,6 +1341,7 @@ impl ChainChessContract {
             .unwrap_or_default()
             .unwrap_or_else(|| PlayerStats::new(chain_id));
         f(&mut stats);
+        stats.last_active = self.runtime.system_time();
         self.state
             .leaderboard
             .insert(&chain_id, stats)
@@ -326,19 +1349,60 @@ impl ChainChessContract {
     }
 
     fn player_chain(&self, game: &StoredGame, color: PlayerColor) -> Option<ChainId> {
+        game.seat(color)
+    }
+
+    /// The seat a joiner would take: whichever one the creator left empty.
+    fn open_seat(game: &StoredGame) -> Option<PlayerColor> {
+        [PlayerColor::White, PlayerColor::Black]
+            .into_iter()
+            .find(|color| game.seat(*color).is_none())
+    }
+
+    /// Marks `game` active, starting the side to move's clock if it is timed.
+    fn start_game(game: &mut StoredGame, now: Timestamp) {
+        game.status = GameStatus::Active;
+        game.last_move_at = Some(now);
+    }
+
+    /// Rating the AI counts as when searching `level` plies deep.
+    fn ai_rating(level: u8) -> i32 {
+        AI_BASE_RATING + (i32::from(level) - i32::from(AI_SEARCH_DEPTH)) * AI_RATING_PER_LEVEL
+    }
+
+    /// Gives both sides a full clock under `control`.
+    fn set_time_control(game: &mut StoredGame, control: TimeControl) {
+        game.white_time_ms = Some(control.initial_ms);
+        game.black_time_ms = Some(control.initial_ms);
+        game.increment_ms = control.increment_ms;
+        game.time_control = Some(control);
+    }
+
+    fn seat_player(game: &mut StoredGame, color: PlayerColor, chain: ChainId) {
         match color {
-            PlayerColor::White => Some(game.white),
-            PlayerColor::Black => game.black,
+            PlayerColor::White => game.white = Some(chain),
+            PlayerColor::Black => game.black = Some(chain),
         }
     }
 
-    async fn list_games_for_chain(&self, chain: ChainId, finished: bool) -> usize {
+    /// Games in which `chain` holds either seat, counting finished ones only
+    /// when `finished` is set. Training games and real ones are counted
+    /// separately, as `training` selects; training games count for the
+    /// chain that created them, since the AI holds both of their seats.
+    async fn list_games_for_chain(&self, chain: ChainId, finished: bool, training: bool) -> usize {
         match self.state.active_games.indices().await {
             Ok(indices) => {
                 let mut count = 0;
                 for id in indices {
                     if let Ok(Some(game)) = self.state.active_games.get(&id).await {
-                        if game.white == chain && (finished || game.status != GameStatus::Finished)
+                        let involved = if game.training {
+                            game.creator == chain
+                        } else {
+                            game.color_of(chain).is_some()
+                        };
+                        if involved
+                            && game.training == training
+                            && (finished || game.status != GameStatus::Finished)
                         {
                             count += 1;
                         }
@@ -349,186 +1413,1315 @@ impl ChainChessContract {
             Err(_) => 0,
         }
     }
+}
 
-    fn apply_uci_move(
-        current_fen: &str,
-        raw_uci: &str,
-        promotion: Option<&str>,
-    ) -> Result<MoveComputation, ()> {
-        let fen_board = Board::from_str(current_fen).map_err(|_| ())?;
-        let mut uci = raw_uci.to_lowercase();
-        if uci.len() == 4 {
-            if let Some(promo) = promotion {
-                uci.push(promo.chars().next().unwrap_or('q'));
-            }
-        }
-        let chess_move = Self::parse_uci_move(&uci)?;
-        if !MoveGen::new_legal(&fen_board).any(|legal| legal == chess_move) {
-            return Err(());
-        }
-        let board_after = fen_board.make_move_new(chess_move);
-        let status = board_after.status();
-        let result = match status {
-            BoardStatus::Ongoing => None,
-            BoardStatus::Stalemate => Some(MatchResult::Draw),
-            BoardStatus::Checkmate => {
-                // In the resulting board it's the opponent's turn but already checkmated.
-                let winner = fen_board.side_to_move();
-                let player_color = if winner == Color::White {
-                    PlayerColor::White
-                } else {
-                    PlayerColor::Black
-                };
-                Some(MatchResult::Winner(player_color))
-            }
+#[cfg(test)]
+mod tests {
+    use async_graphql::{value, EmptyMutation, EmptySubscription, Schema, Value};
+    use chainchess::GameSummary;
+    use linera_sdk::{linera_base_types::CryptoHash, util::BlockingWait};
+
+    use super::*;
+
+    fn chain(name: &str) -> ChainId {
+        ChainId(CryptoHash::test_hash(name))
+    }
+
+    /// A freshly instantiated contract on a mock runtime with `parameters`,
+    /// at time zero.
+    fn contract_with(parameters: ChainChessParameters) -> ChainChessContract {
+        let runtime = ContractRuntime::new()
+            .with_application_parameters(parameters)
+            .with_chain_id(chain("white"))
+            .with_system_time(0.into());
+        let state = ChainChessState::load(runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to load state");
+        let mut contract = ChainChessContract { state, runtime };
+        contract.instantiate(()).blocking_wait();
+        contract
+    }
+
+    fn contract() -> ChainChessContract {
+        contract_with(ChainChessParameters::default())
+    }
+
+    /// Runs `operation` as submitted from the chain named `caller`.
+    fn execute(
+        contract: &mut ChainChessContract,
+        caller: &str,
+        operation: Operation,
+    ) -> ChainChessResponse {
+        contract.runtime.set_chain_id(chain(caller));
+        contract.execute_operation(operation).blocking_wait()
+    }
+
+    /// Like `execute`, but the operation must succeed and return a game.
+    fn execute_ok(
+        contract: &mut ChainChessContract,
+        caller: &str,
+        operation: Operation,
+    ) -> GameSummary {
+        let response = execute(contract, caller, operation);
+        assert!(response.success, "{}", response.message);
+        response.game.expect("the response should carry the game")
+    }
+
+    fn create(contract: &mut ChainChessContract, creator: &str, options: GameOptions) -> u64 {
+        let operation = Operation::CreateGame {
+            metadata: None,
+            play_vs_ai: false,
+            options: Some(options),
         };
-        // Generate SAN notation
-        let san = Self::generate_san(&fen_board, chess_move);
-        
-        Ok(MoveComputation {
-            fen: board_after.to_string(),
-            uci,
-            san: Some(san),
-            result,
-        })
+        execute_ok(contract, creator, operation).game_id
     }
 
-    fn parse_uci_move(uci: &str) -> Result<ChessMove, ()> {
-        if uci.len() < 4 {
-            return Err(());
-        }
-        let from = Square::from_str(&uci[0..2]).map_err(|_| ())?;
-        let to = Square::from_str(&uci[2..4]).map_err(|_| ())?;
-        let promo_piece = if uci.len() > 4 {
-            Some(Self::promotion_piece(uci.chars().last().unwrap_or('q'))?)
-        } else {
-            None
+    /// A game `white` created and `black` joined.
+    fn start(contract: &mut ChainChessContract, white: &str, black: &str) -> u64 {
+        let game_id = create(contract, white, GameOptions::default());
+        execute_ok(contract, black, Operation::JoinGame { game_id });
+        game_id
+    }
+
+    /// Answers the GraphQL `query` the way the service would, from the state
+    /// as saved after the operations run so far.
+    fn query(contract: &mut ChainChessContract, query: &str) -> Value {
+        contract
+            .state
+            .save()
+            .blocking_wait()
+            .expect("Failed to save state");
+        let state = ChainChessState::load(contract.runtime.root_view_storage_context())
+            .blocking_wait()
+            .expect("Failed to load state");
+        let schema = Schema::build(state, EmptyMutation, EmptySubscription)
+            .data(contract.runtime.system_time())
+            .data(contract.runtime.application_parameters())
+            .finish();
+        let response = schema.execute(query).blocking_wait();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data
+    }
+
+    fn stored(contract: &ChainChessContract, game_id: u64) -> StoredGame {
+        contract
+            .state
+            .active_games
+            .get(&game_id)
+            .blocking_wait()
+            .unwrap()
+            .expect("the game should be stored")
+    }
+
+    fn play(contract: &mut ChainChessContract, caller: &str, game_id: u64, uci: &str) {
+        let operation = Operation::SubmitMove {
+            game_id,
+            uci: uci.to_string(),
+            promotion: None,
         };
-        Ok(ChessMove::new(from, to, promo_piece))
+        execute_ok(contract, caller, operation);
     }
 
-    fn promotion_piece(letter: char) -> Result<Piece, ()> {
-        match letter.to_ascii_lowercase() {
-            'q' => Ok(Piece::Queen),
-            'r' => Ok(Piece::Rook),
-            'b' => Ok(Piece::Bishop),
-            'n' => Ok(Piece::Knight),
-            _ => Err(()),
+    /// Plays `moves` in `game_id`, alternating between `white` and `black`.
+    fn play_line(
+        contract: &mut ChainChessContract,
+        (white, black): (&str, &str),
+        game_id: u64,
+        moves: &[&str],
+    ) {
+        for (ply, uci) in moves.iter().enumerate() {
+            let caller = if ply % 2 == 0 { white } else { black };
+            play(contract, caller, game_id, uci);
         }
     }
 
-    fn pick_ai_move(fen: &str) -> Option<String> {
-        let board = Board::from_str(fen).ok()?;
-        let mut best_move = None;
-        let mut best_score = i32::MIN;
-        for mv in MoveGen::new_legal(&board) {
-            let score = Self::score_move(&board, mv);
-            if score > best_score {
-                best_score = score;
-                best_move = Some(mv);
+    /// The error message `error` produces in a response.
+    fn error_message(error: ChainChessError) -> String {
+        ChainChessResponse::from_error(error).message
+    }
+
+    fn create_vs_ai(contract: &mut ChainChessContract, creator: &str, options: GameOptions) -> u64 {
+        let operation = Operation::CreateGame {
+            metadata: None,
+            play_vs_ai: true,
+            options: Some(options),
+        };
+        execute_ok(contract, creator, operation).game_id
+    }
+
+    /// An active game between two chains after `moves` from the start.
+    fn played_game(moves: &[&str]) -> StoredGame {
+        let mut game =
+            ChainChessContract::new_game(1, chain("white"), DEFAULT_FEN.to_string(), 0.into());
+        ChainChessContract::seat_player(&mut game, PlayerColor::Black, chain("black"));
+        ChainChessContract::start_game(&mut game, 0.into());
+        for (ply, uci) in moves.iter().enumerate() {
+            let outcome = apply_uci_move(&game.board_fen, uci, None).unwrap();
+            let played_at = Timestamp::from(ply as u64 + 1);
+            if let Some(result) = game.record_move(outcome, played_at, None) {
+                game.finish(result, played_at);
             }
         }
-        best_move.map(|mv| Self::move_to_uci_string(mv))
+        game
     }
 
-    fn score_move(board: &Board, mv: ChessMove) -> i32 {
-        let mut score = 0;
-        if let Some(piece) = board.piece_on(mv.get_dest()) {
-            score += Self::piece_value(piece);
-        }
-        if mv.get_promotion().is_some() {
-            score += 5;
+    #[test]
+    fn audit_reports_tampered_moves() {
+        let game = played_game(&["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(game.audit(&game.moves), (3, None));
+
+        let mut history = game.moves.clone();
+        history[1].san = Some("d5".to_string());
+        let (plies, issue) = game.audit(&history);
+        assert_eq!(plies, 1);
+        assert_eq!(
+            issue.as_deref(),
+            Some("move 2 (e7e5) is recorded with the wrong SAN")
+        );
+
+        let mut history = game.moves.clone();
+        history[2].uci = "g1g3".to_string();
+        assert_eq!(
+            game.audit(&history).1.as_deref(),
+            Some("move 3 (g1g3) is illegal")
+        );
+
+        let mut tampered = game.clone();
+        tampered.board_fen = DEFAULT_FEN.to_string();
+        assert_eq!(
+            tampered.audit(&game.moves).1.as_deref(),
+            Some("stored board does not match the replayed moves")
+        );
+        assert!(tampered.verify(&game.moves).is_err());
+    }
+
+    #[test]
+    fn result_token_follows_the_outcome() {
+        let scholars_mate = ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"];
+        assert_eq!(played_game(&scholars_mate).result_token(), "1-0");
+        let fools_mate = ["f2f3", "e7e5", "g2g4", "d8h4"];
+        assert_eq!(played_game(&fools_mate).result_token(), "0-1");
+
+        let mut drawn = played_game(&["e2e4"]);
+        assert_eq!(drawn.result_token(), "*");
+        drawn.finish(MatchResult::Draw(TerminationReason::Agreement), 2.into());
+        assert_eq!(drawn.result_token(), "1/2-1/2");
+
+        let lobby =
+            ChainChessContract::new_game(2, chain("white"), DEFAULT_FEN.to_string(), 0.into());
+        assert_eq!(lobby.result_token(), "*");
+    }
+
+    #[test]
+    fn zeroed_game_counter_is_repaired_before_creating_a_game() {
+        let mut contract = contract();
+        contract.state.next_game_id.set(0);
+        assert_eq!(create(&mut contract, "white", GameOptions::default()), 1);
+        assert_eq!(*contract.state.next_game_id.get(), 2);
+        assert_eq!(create(&mut contract, "white", GameOptions::default()), 2);
+    }
+
+    #[test]
+    fn games_as_filters_by_seat() {
+        let mut contract = contract();
+        let as_white = start(&mut contract, "carol", "dave");
+        let as_black = start(&mut contract, "erin", "carol");
+        let carol = chain("carol");
+        let games = query(
+            &mut contract,
+            &format!(
+                "{{ white: gamesAs(chainId: \"{carol}\", color: WHITE) {{ gameId }} \
+                 black: gamesAs(chainId: \"{carol}\", color: BLACK) {{ gameId }} }}"
+            ),
+        );
+        assert_eq!(
+            games,
+            value!({
+                "white": [{ "gameId": as_white }],
+                "black": [{ "gameId": as_black }],
+            })
+        );
+    }
+
+    #[test]
+    fn preview_move_leaves_the_stored_game_alone() {
+        let mut contract = contract();
+        let game_id = start(&mut contract, "white", "black");
+        let after_e4 = apply_uci_move(DEFAULT_FEN, "e2e4", None).unwrap().fen;
+        let preview = query(
+            &mut contract,
+            &format!(
+                "{{ legal: previewMove(gameId: {game_id}, uci: \"e2e4\") {{ boardFen turn moveCount }} \
+                 illegal: previewMove(gameId: {game_id}, uci: \"e2e5\") {{ boardFen }} \
+                 stored: game(id: {game_id}) {{ boardFen turn moveCount }} }}"
+            ),
+        );
+        assert_eq!(
+            preview,
+            value!({
+                "legal": { "boardFen": after_e4, "turn": "BLACK", "moveCount": 1 },
+                "illegal": null,
+                "stored": { "boardFen": DEFAULT_FEN, "turn": "WHITE", "moveCount": 0 },
+            })
+        );
+        assert_eq!(stored(&contract, game_id).board_fen, DEFAULT_FEN);
+    }
+
+    #[test]
+    fn records_pick_the_longest_game_and_the_fastest_mate() {
+        let mut contract = contract();
+        let players = ("white", "black");
+        let fools_mate = start(&mut contract, "white", "black");
+        play_line(
+            &mut contract,
+            players,
+            fools_mate,
+            &["f2f3", "e7e5", "g2g4", "d8h4"],
+        );
+        let resigned = start(&mut contract, "white", "black");
+        play_line(&mut contract, players, resigned, &["e2e4", "e7e5"]);
+        execute_ok(
+            &mut contract,
+            "white",
+            Operation::Resign { game_id: resigned },
+        );
+        let long_game = start(&mut contract, "white", "black");
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"].repeat(20);
+        play_line(&mut contract, players, long_game, &shuffle);
+        execute_ok(
+            &mut contract,
+            "black",
+            Operation::Resign { game_id: long_game },
+        );
+
+        let records = query(
+            &mut contract,
+            "{ records { longestGame { gameId plies winner } fastestWin { gameId plies winner } } }",
+        );
+        assert_eq!(
+            records,
+            value!({
+                "records": {
+                    "longestGame": { "gameId": long_game, "plies": 80, "winner": "WHITE" },
+                    "fastestWin": { "gameId": fools_mate, "plies": 4, "winner": "BLACK" },
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn rejected_joiner_is_cleared_and_the_lobby_stays_open() {
+        let mut contract = contract();
+        let private = GameOptions {
+            private_approval: Some(true),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "host", private);
+        let pending = execute_ok(&mut contract, "guest", Operation::JoinGame { game_id });
+        assert_eq!(pending.status, GameStatus::Lobby);
+        assert_eq!(pending.pending_joiner, Some(chain("guest")));
+        let response = execute(&mut contract, "rival", Operation::JoinGame { game_id });
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::NotJoinable(game_id))
+        );
+        let response = execute(
+            &mut contract,
+            "guest",
+            Operation::ApproveJoin {
+                game_id,
+                accept: true,
+            },
+        );
+        assert_eq!(response.message, error_message(ChainChessError::NotHost));
+
+        let rejected = execute_ok(
+            &mut contract,
+            "host",
+            Operation::ApproveJoin {
+                game_id,
+                accept: false,
+            },
+        );
+        assert_eq!(rejected.status, GameStatus::Lobby);
+        assert_eq!(rejected.pending_joiner, None);
+        assert_eq!(rejected.black, None);
+
+        execute_ok(&mut contract, "rival", Operation::JoinGame { game_id });
+        let approved = execute_ok(
+            &mut contract,
+            "host",
+            Operation::ApproveJoin {
+                game_id,
+                accept: true,
+            },
+        );
+        assert_eq!(approved.status, GameStatus::Active);
+        assert_eq!(approved.black, Some(chain("rival")));
+    }
+
+    #[test]
+    fn creator_is_reported_apart_from_the_white_seat() {
+        let mut contract = contract();
+        let as_black = GameOptions {
+            creator_color: Some(PlayerColor::Black),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "host", as_black);
+        let joined = execute_ok(&mut contract, "guest", Operation::JoinGame { game_id });
+        assert_eq!(joined.creator, chain("host"));
+        assert_eq!(joined.black, Some(chain("host")));
+        assert_eq!(joined.white, Some(chain("guest")));
+    }
+
+    #[test]
+    fn resume_games_lists_my_turn_first_then_most_recent() {
+        let mut contract = contract();
+        let waiting = start(&mut contract, "carol", "dave");
+        contract.runtime.set_system_time(10.into());
+        let my_turn_older = start(&mut contract, "carol", "erin");
+        play(&mut contract, "carol", waiting, "e2e4");
+        contract.runtime.set_system_time(20.into());
+        let my_turn_newer = start(&mut contract, "frank", "carol");
+        play(&mut contract, "frank", my_turn_newer, "e2e4");
+        create(&mut contract, "carol", GameOptions::default());
+
+        let carol = chain("carol");
+        let games = query(
+            &mut contract,
+            &format!("{{ resumeGames(chainId: \"{carol}\") {{ gameId }} }}"),
+        );
+        assert_eq!(
+            games,
+            value!({
+                "resumeGames": [
+                    { "gameId": my_turn_newer },
+                    { "gameId": my_turn_older },
+                    { "gameId": waiting },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn seeded_training_games_belong_to_the_ai() {
+        let mut contract = contract_with(ChainChessParameters {
+            max_open_games: 3,
+            ..ChainChessParameters::default()
+        });
+        execute_ok(
+            &mut contract,
+            "coach",
+            Operation::SeedTrainingGames { count: 2 },
+        );
+        let training = contract
+            .state
+            .active_games
+            .indices()
+            .blocking_wait()
+            .unwrap();
+        assert_eq!(training, [1, 2]);
+        for game_id in training {
+            let game = stored(&contract, game_id);
+            assert!(game.training && game.ai_white && game.ai_black);
+            assert_eq!((game.white, game.black), (None, None));
+            assert_eq!(game.creator, chain("coach"));
+            assert_eq!(game.status, GameStatus::Active);
+            let response = execute(&mut contract, "coach", Operation::Resign { game_id });
+            assert_eq!(
+                response.message,
+                error_message(ChainChessError::NotParticipant)
+            );
         }
-        score += Self::square_bonus(mv.get_dest());
-        score
+
+        // The allowance is counted by creator, apart from real games.
+        let response = execute(
+            &mut contract,
+            "coach",
+            Operation::SeedTrainingGames { count: 2 },
+        );
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::LobbyLimitReached(3))
+        );
+        execute_ok(
+            &mut contract,
+            "coach",
+            Operation::SeedTrainingGames { count: 1 },
+        );
+        create(&mut contract, "coach", GameOptions::default());
+
+        let listed = query(
+            &mut contract,
+            "{ games { gameId } openLobbies { gameId } trainingGames { gameId } }",
+        );
+        assert_eq!(
+            listed,
+            value!({
+                "games": [{ "gameId": 4 }],
+                "openLobbies": [{ "gameId": 4 }],
+                "trainingGames": [{ "gameId": 1 }, { "gameId": 2 }, { "gameId": 3 }],
+            })
+        );
+    }
+
+    #[test]
+    fn play_state_bundles_the_individual_queries() {
+        let mut contract = contract();
+        let game_id = start(&mut contract, "white", "black");
+        play_line(
+            &mut contract,
+            ("white", "black"),
+            game_id,
+            &["e2e4", "f7f6", "d1h5"],
+        );
+        let data = query(
+            &mut contract,
+            &format!(
+                "{{ playState(gameId: {game_id}) {{ boardFen turn inCheck legalMoves status }} \
+                 game(id: {game_id}) {{ boardFen turn inCheck status }} \
+                 legalMoves(gameId: {game_id}) \
+                 unknown: playState(gameId: 99) {{ status }} }}"
+            ),
+        );
+        let Value::Object(data) = data else {
+            panic!("the query should return an object");
+        };
+        let Value::Object(game) = &data["game"] else {
+            panic!("the game should be found");
+        };
+        assert_eq!(data["legalMoves"], value!(["g7g6"]));
+        assert_eq!(
+            data["playState"],
+            value!({
+                "boardFen": game["boardFen"].clone(),
+                "turn": game["turn"].clone(),
+                "inCheck": true,
+                "legalMoves": data["legalMoves"].clone(),
+                "status": game["status"].clone(),
+            })
+        );
+        assert_eq!(game["inCheck"], value!(true));
+        assert_eq!(data["unknown"], Value::Null);
     }
 
-    fn piece_value(piece: Piece) -> i32 {
-        match piece {
-            Piece::Pawn => 1,
-            Piece::Knight | Piece::Bishop => 3,
-            Piece::Rook => 5,
-            Piece::Queen => 9,
-            Piece::King => 0,
+    #[test]
+    fn engine_info_matches_what_the_contract_does() {
+        let mut contract = contract();
+        let info = query(
+            &mut contract,
+            "{ engineInfo { defaultDepth variants clocks threefoldRepetition features } }",
+        );
+        let Value::Object(mut info) = info else {
+            panic!("the query should return an object");
+        };
+        let Value::Object(info) = info.swap_remove("engineInfo").unwrap() else {
+            panic!("engineInfo should be an object");
+        };
+        assert_eq!(info["defaultDepth"], value!(AI_SEARCH_DEPTH));
+        assert_eq!(info["variants"], value!(["standard"]));
+        let Value::List(features) = &info["features"] else {
+            panic!("features should be a list");
+        };
+        for feature in ["training_games", "takebacks", "ai_levels"] {
+            assert!(features.contains(&value!(feature)), "{feature}");
         }
+
+        // Clocks.
+        assert_eq!(info["clocks"], value!(true));
+        let control = TimeControl {
+            initial_ms: 60_000,
+            increment_ms: 1_000,
+        };
+        let timed = GameOptions {
+            time_control: Some(control),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "white", timed);
+        assert_eq!(stored(&contract, game_id).white_time_ms, Some(60_000));
+
+        // Threefold repetition.
+        assert_eq!(info["threefoldRepetition"], value!(true));
+        let game_id = start(&mut contract, "white", "black");
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"].repeat(2);
+        play_line(&mut contract, ("white", "black"), game_id, &shuffle);
+        let drawn = execute_ok(&mut contract, "white", Operation::ClaimDraw { game_id });
+        assert_eq!(drawn.termination, Some(TerminationReason::Threefold));
+
+        // AI levels, training games and takebacks.
+        let strongest = GameOptions {
+            ai_level: Some(u8::MAX),
+            ..GameOptions::default()
+        };
+        let game_id = create_vs_ai(&mut contract, "white", strongest);
+        assert_eq!(stored(&contract, game_id).ai_level, MAX_AI_LEVEL);
+        execute_ok(
+            &mut contract,
+            "white",
+            Operation::SeedTrainingGames { count: 1 },
+        );
+        play(&mut contract, "white", game_id, "e2e4");
+        let undone = execute_ok(
+            &mut contract,
+            "white",
+            Operation::RequestTakeback { game_id },
+        );
+        assert_eq!(undone.move_count, 0);
     }
 
-    fn square_bonus(square: Square) -> i32 {
-        let file = square.get_file().to_index() as i32;
-        let rank = square.get_rank().to_index() as i32;
-        if (file == 3 || file == 4) && (rank == 3 || rank == 4) {
-            2
-        } else if (2..=5).contains(&file) && (2..=5).contains(&rank) {
-            1
-        } else {
-            0
+    #[test]
+    fn beating_stronger_players_lifts_the_performance_rating() {
+        let mut contract = contract();
+        let opponents = ["ann", "bob", "cat"];
+        for name in opponents {
+            let mut stats = PlayerStats::new(chain(name));
+            stats.rating = 1600;
+            contract
+                .state
+                .leaderboard
+                .insert(&chain(name), stats)
+                .unwrap();
         }
+        for name in opponents {
+            let game_id = start(&mut contract, "underdog", name);
+            execute_ok(&mut contract, name, Operation::Resign { game_id });
+        }
+
+        let underdog = chain("underdog");
+        let stored_rating = contract.state.rating_of(underdog).blocking_wait();
+        let mut opponent_total = 0;
+        for name in opponents {
+            opponent_total += contract.state.rating_of(chain(name)).blocking_wait();
+        }
+        let performance = query(
+            &mut contract,
+            &format!("{{ performanceRating(chainId: \"{underdog}\") }}"),
+        );
+        let expected = (opponent_total + 400 * 3) / 3;
+        assert_eq!(performance, value!({ "performanceRating": expected }));
+        assert!(
+            expected > stored_rating + 400,
+            "{expected} vs {stored_rating}"
+        );
     }
 
-    fn move_to_uci_string(mv: ChessMove) -> String {
-        let mut result = format!("{}{}", mv.get_source(), mv.get_dest());
-        if let Some(promo) = mv.get_promotion() {
-            let letter = match promo {
-                Piece::Queen => 'q',
-                Piece::Rook => 'r',
-                Piece::Bishop => 'b',
-                Piece::Knight => 'n',
-                _ => 'q',
-            };
-            result.push(letter);
-        }
-        result
-    }
-
-    fn generate_san(board: &Board, mv: ChessMove) -> String {
-        // Simple SAN generation - in a production system you'd use a proper chess library
-        let from = mv.get_source();
-        let to = mv.get_dest();
-        let piece = board.piece_on(from);
-        
-        let piece_char = match piece {
-            Some(Piece::King) => 'K',
-            Some(Piece::Queen) => 'Q',
-            Some(Piece::Rook) => 'R',
-            Some(Piece::Bishop) => 'B',
-            Some(Piece::Knight) => 'N',
-            _ => ' ',
-        };
-        
-        let from_sq = format!("{}", from);
-        let to_sq = format!("{}", to);
-        
-        if let Some(_captured) = board.piece_on(to) {
-            if piece_char == ' ' {
-                format!("{}{}", from_sq.chars().next().unwrap(), to_sq)
+    #[test]
+    fn min_rating_keeps_weaker_chains_out() {
+        let mut contract = contract();
+        let mut expert = PlayerStats::new(chain("expert"));
+        expert.rating = 1500;
+        contract
+            .state
+            .leaderboard
+            .insert(&chain("expert"), expert)
+            .unwrap();
+        let gated = GameOptions {
+            min_rating: Some(1400),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "host", gated);
+        assert_eq!(stored(&contract, game_id).min_rating, Some(1400));
+
+        let response = execute(&mut contract, "novice", Operation::JoinGame { game_id });
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::RatingTooLow(1400))
+        );
+        let joined = execute_ok(&mut contract, "expert", Operation::JoinGame { game_id });
+        assert_eq!(joined.black, Some(chain("expert")));
+        assert_eq!(joined.min_rating, Some(1400));
+    }
+
+    #[test]
+    fn move_tree_branches_after_a_shared_prefix() {
+        let mut contract = contract();
+        let players = ("white", "black");
+        let lines = [
+            (["e2e4", "e7e5", "g1f3"], "black"),
+            (["e2e4", "e7e5", "g1f3"], ""),
+            (["e2e4", "e7e5", "f1c4"], "white"),
+            (["d2d4", "d7d5", "c2c4"], "black"),
+        ];
+        for (moves, resigner) in lines {
+            let game_id = start(&mut contract, "white", "black");
+            play_line(&mut contract, players, game_id, &moves);
+            if resigner.is_empty() {
+                execute_ok(&mut contract, "black", Operation::OfferDraw { game_id });
+                execute_ok(&mut contract, "white", Operation::AcceptDraw { game_id });
             } else {
-                format!("{}x{}", piece_char, to_sq)
+                execute_ok(&mut contract, resigner, Operation::Resign { game_id });
             }
-        } else if let Some(promo) = mv.get_promotion() {
-            let promo_char = match promo {
-                Piece::Queen => 'Q',
-                Piece::Rook => 'R',
-                Piece::Bishop => 'B',
-                Piece::Knight => 'N',
-                _ => 'Q',
-            };
-            format!("{}{}={}", from_sq, to_sq, promo_char)
-        } else if piece_char != ' ' {
-            format!("{}{}", piece_char, to_sq)
-        } else {
-            format!("{}{}", from_sq, to_sq)
         }
+        // Unfinished games are left out.
+        let ongoing = start(&mut contract, "white", "black");
+        play_line(&mut contract, players, ongoing, &["e2e4", "e7e5", "b1c3"]);
+
+        let tree = query(
+            &mut contract,
+            "{ moveTree(prefixUci: [\"e2e4\", \"e7e5\"], depth: 1) \
+             { line games whiteWins blackWins draws whiteWinRate } }",
+        );
+        assert_eq!(
+            tree,
+            value!({
+                "moveTree": [
+                    {
+                        "line": ["g1f3"], "games": 2, "whiteWins": 1, "blackWins": 0,
+                        "draws": 1, "whiteWinRate": 0.5,
+                    },
+                    {
+                        "line": ["f1c4"], "games": 1, "whiteWins": 0, "blackWins": 1,
+                        "draws": 0, "whiteWinRate": 0.0,
+                    },
+                ]
+            })
+        );
     }
-}
 
-enum MatchResult {
-    Winner(PlayerColor),
-    Draw,
-}
+    #[test]
+    fn my_counts_tallies_each_status() {
+        let mut contract = contract();
+        create(&mut contract, "carol", GameOptions::default());
+        start(&mut contract, "dave", "carol");
+        let finished = start(&mut contract, "carol", "erin");
+        execute_ok(
+            &mut contract,
+            "erin",
+            Operation::Resign { game_id: finished },
+        );
+        // Games carol is not in do not count.
+        start(&mut contract, "dave", "erin");
+
+        let carol = chain("carol");
+        let counts = query(
+            &mut contract,
+            &format!("{{ myCounts(chainId: \"{carol}\") {{ lobby active finished }} }}"),
+        );
+        assert_eq!(
+            counts,
+            value!({ "myCounts": { "lobby": 1, "active": 1, "finished": 1 } })
+        );
+    }
+
+    #[test]
+    fn fork_starts_from_the_source_position_at_the_ply() {
+        let mut contract = contract();
+        let moves = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"];
+        let game_id = start(&mut contract, "white", "black");
+        play_line(&mut contract, ("white", "black"), game_id, &moves);
+        let fork = Operation::ForkForAnalysis { game_id, at_ply: 4 };
+        let response = execute(&mut contract, "white", fork);
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::NotFinished(game_id))
+        );
+        execute_ok(&mut contract, "black", Operation::Resign { game_id });
+
+        let fork = execute_ok(
+            &mut contract,
+            "white",
+            Operation::ForkForAnalysis { game_id, at_ply: 4 },
+        );
+        let midgame = replay_fen(DEFAULT_FEN, moves[..4].iter().copied()).unwrap();
+        let source = stored(&contract, game_id);
+        assert_eq!(
+            source.board_at_ply(&source.moves, 4).as_ref(),
+            Some(&midgame)
+        );
+        assert_eq!(fork.initial_fen, midgame);
+        assert_eq!(fork.board_fen, midgame);
+        assert_eq!(fork.status, GameStatus::Active);
+        assert!(fork.ai_black && !fork.rated);
+
+        let end = execute_ok(
+            &mut contract,
+            "white",
+            Operation::ForkForAnalysis { game_id, at_ply: 6 },
+        );
+        assert_eq!(end.initial_fen, source.board_fen);
+        let response = execute(
+            &mut contract,
+            "white",
+            Operation::ForkForAnalysis { game_id, at_ply: 7 },
+        );
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::InvalidPly(7))
+        );
+    }
+
+    #[test]
+    fn archived_moves_come_back_in_the_full_history() {
+        let mut contract = contract_with(ChainChessParameters {
+            verify_on_load: true,
+            ..ChainChessParameters::default()
+        });
+        let game_id = start(&mut contract, "white", "black");
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"].repeat(70);
+        play_line(&mut contract, ("white", "black"), game_id, &shuffle);
+
+        let game = stored(&contract, game_id);
+        assert!(game.archived_moves > 0);
+        assert!(game.moves.len() <= MAX_STORED_MOVES);
+        assert_eq!(
+            game.archived_moves as usize + game.moves.len(),
+            shuffle.len()
+        );
+        let history = query(
+            &mut contract,
+            &format!("{{ moveHistory(gameId: {game_id}) {{ uci }} }}"),
+        );
+        let expected: Vec<Value> = shuffle.iter().map(|uci| value!({ "uci": uci })).collect();
+        assert_eq!(history, value!({ "moveHistory": expected }));
+        // Loading re-verifies the stitched history.
+        execute_ok(&mut contract, "white", Operation::Resign { game_id });
+    }
+
+    #[test]
+    fn blocking_works_in_both_directions_until_lifted() {
+        let mut contract = contract();
+        let blocked = Operation::BlockPlayer {
+            chain_id: chain("black"),
+        };
+        execute(&mut contract, "white", blocked);
+
+        // The blocked chain can't join the blocker's lobby...
+        let game_id = create(&mut contract, "white", GameOptions::default());
+        let response = execute(&mut contract, "black", Operation::JoinGame { game_id });
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::PlayerBlocked)
+        );
+        // ...and the blocker can't join the blocked chain's lobby either.
+        let other_id = create(&mut contract, "black", GameOptions::default());
+        let response = execute(
+            &mut contract,
+            "white",
+            Operation::JoinGame { game_id: other_id },
+        );
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::PlayerBlocked)
+        );
+
+        let unblocked = Operation::UnblockPlayer {
+            chain_id: chain("black"),
+        };
+        assert!(execute(&mut contract, "white", unblocked).success);
+        execute_ok(&mut contract, "black", Operation::JoinGame { game_id });
+
+        let response = execute(
+            &mut contract,
+            "white",
+            Operation::BlockPlayer {
+                chain_id: chain("white"),
+            },
+        );
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::CannotBlockSelf)
+        );
+    }
 
-struct MoveComputation {
-    fen: String,
-    uci: String,
-    san: Option<String>,
-    result: Option<MatchResult>,
+    #[test]
+    fn last_move_san_follows_the_latest_move() {
+        let mut contract = contract();
+        let game_id = start(&mut contract, "white", "black");
+        let request = format!("{{ lastMoveSan(gameId: {game_id}) }}");
+        assert_eq!(
+            query(&mut contract, &request),
+            value!({ "lastMoveSan": null })
+        );
+
+        play_line(
+            &mut contract,
+            ("white", "black"),
+            game_id,
+            &["e2e4", "d7d5", "e4d5"],
+        );
+        assert_eq!(
+            query(&mut contract, &request),
+            value!({ "lastMoveSan": "exd5" })
+        );
+    }
+
+    #[test]
+    fn draws_are_broken_down_by_termination() {
+        let mut contract = contract();
+        let from = |fen: &str| GameOptions {
+            start_fen: Some(fen.to_string()),
+            ..GameOptions::default()
+        };
+        let join = |contract: &mut ChainChessContract, game_id| {
+            execute_ok(contract, "black", Operation::JoinGame { game_id });
+        };
+
+        let stalemate = create(
+            &mut contract,
+            "white",
+            from("k7/8/8/2Q5/8/8/8/7K w - - 0 1"),
+        );
+        join(&mut contract, stalemate);
+        play(&mut contract, "white", stalemate, "c5b6");
+
+        let bare_kings = create(
+            &mut contract,
+            "white",
+            from("k7/8/8/8/8/2p5/2K5/8 w - - 0 1"),
+        );
+        join(&mut contract, bare_kings);
+        play(&mut contract, "white", bare_kings, "c2c3");
+
+        let fifty = create(
+            &mut contract,
+            "white",
+            from("k7/8/8/8/8/8/8/KR6 w - - 100 80"),
+        );
+        join(&mut contract, fifty);
+        execute_ok(
+            &mut contract,
+            "white",
+            Operation::ClaimDraw { game_id: fifty },
+        );
+
+        let repeated = start(&mut contract, "white", "black");
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"].repeat(2);
+        play_line(&mut contract, ("white", "black"), repeated, &shuffle);
+        execute_ok(
+            &mut contract,
+            "white",
+            Operation::ClaimDraw { game_id: repeated },
+        );
+
+        let agreed = start(&mut contract, "white", "black");
+        execute_ok(
+            &mut contract,
+            "white",
+            Operation::OfferDraw { game_id: agreed },
+        );
+        execute_ok(
+            &mut contract,
+            "black",
+            Operation::AcceptDraw { game_id: agreed },
+        );
+
+        let decisive = start(&mut contract, "white", "black");
+        execute_ok(
+            &mut contract,
+            "black",
+            Operation::Resign { game_id: decisive },
+        );
+
+        assert_eq!(
+            query(
+                &mut contract,
+                "{ drawsByReason { repetition fiftyMove stalemate insufficientMaterial agreement other } }"
+            ),
+            value!({
+                "drawsByReason": {
+                    "repetition": 1,
+                    "fiftyMove": 1,
+                    "stalemate": 1,
+                    "insufficientMaterial": 1,
+                    "agreement": 1,
+                    "other": 0,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn preferences_fill_in_the_options_a_game_leaves_out() {
+        let mut contract = contract();
+        let clock = |game: &StoredGame| {
+            game.time_control
+                .map(|control| (control.initial_ms, control.increment_ms))
+        };
+        let blitz = TimeControl {
+            initial_ms: 180_000,
+            increment_ms: 2_000,
+        };
+        let operation = Operation::SetPreferences {
+            default_time_control: Some(blitz),
+            default_rated: false,
+        };
+        assert!(execute(&mut contract, "white", operation).success);
+
+        let game_id = create(&mut contract, "white", GameOptions::default());
+        let game = stored(&contract, game_id);
+        assert_eq!(clock(&game), Some((180_000, 2_000)));
+        assert!(!game.rated);
+        // An explicit clock still wins over the default.
+        let rapid = TimeControl {
+            initial_ms: 600_000,
+            increment_ms: 0,
+        };
+        let options = GameOptions {
+            time_control: Some(rapid),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "white", options);
+        assert_eq!(clock(&stored(&contract, game_id)), Some((600_000, 0)));
+        // Other chains keep the defaults.
+        let game_id = create(&mut contract, "black", GameOptions::default());
+        let game = stored(&contract, game_id);
+        assert_eq!(clock(&game), None);
+        assert!(game.rated);
+
+        let request = format!(
+            "{{ preferences(chain: \"{}\") {{ defaultRated defaultTimeControl {{ initialMs incrementMs }} }} }}",
+            chain("white")
+        );
+        assert_eq!(
+            query(&mut contract, &request),
+            value!({
+                "preferences": {
+                    "defaultRated": false,
+                    "defaultTimeControl": { "initialMs": 180_000, "incrementMs": 2_000 },
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn game_diff_carries_only_the_moves_a_client_missed() {
+        let mut contract = contract();
+        let game_id = start(&mut contract, "white", "black");
+        play(&mut contract, "white", game_id, "e2e4");
+        let seen = stored(&contract, game_id).version;
+        let diff = |contract: &mut ChainChessContract, since: u64| {
+            let request = format!(
+                "{{ gameDiff(gameId: {game_id}, sinceVersion: {since}) {{ turn moveCount newMoves {{ uci }} }} }}"
+            );
+            query(contract, &request)
+        };
+        assert_eq!(diff(&mut contract, seen), value!({ "gameDiff": null }));
+
+        play(&mut contract, "black", game_id, "e7e5");
+        assert_eq!(
+            diff(&mut contract, seen),
+            value!({
+                "gameDiff": {
+                    "turn": "WHITE",
+                    "moveCount": 2,
+                    "newMoves": [{ "uci": "e7e5" }],
+                }
+            })
+        );
+        assert_eq!(
+            diff(&mut contract, 0),
+            value!({
+                "gameDiff": {
+                    "turn": "WHITE",
+                    "moveCount": 2,
+                    "newMoves": [{ "uci": "e2e4" }, { "uci": "e7e5" }],
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn scripted_moves_apply_all_or_nothing_and_only_to_unrated_lobbies() {
+        let mut contract = contract();
+        let script = |game_id, moves: &[&str]| Operation::PlayMoves {
+            game_id,
+            moves: moves.iter().map(|uci| uci.to_string()).collect(),
+        };
+
+        let rated = create(&mut contract, "white", GameOptions::default());
+        let response = execute(&mut contract, "white", script(rated, &["e2e4"]));
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::NotScriptable)
+        );
+
+        let unrated = Operation::SetPreferences {
+            default_time_control: None,
+            default_rated: false,
+        };
+        execute(&mut contract, "white", unrated);
+        let game_id = create(&mut contract, "white", GameOptions::default());
+        let response = execute(&mut contract, "black", script(game_id, &["e2e4"]));
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::NotScriptable)
+        );
+
+        let line = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"];
+        let game = execute_ok(&mut contract, "white", script(game_id, &line));
+        assert_eq!(
+            game.board_fen,
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3"
+        );
+
+        // The illegal last move discards the legal ones before it.
+        let response = execute(&mut contract, "white", script(game_id, &["a7a6", "b5b5"]));
+        assert!(!response.success);
+        let game = stored(&contract, game_id);
+        assert_eq!(game.moves.len(), line.len());
+        assert_eq!(
+            game.board_fen,
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3"
+        );
+
+        let too_many = vec!["e2e4"; MAX_SCRIPTED_MOVES + 1];
+        let response = execute(&mut contract, "white", script(game_id, &too_many));
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::TooManyMoves)
+        );
+    }
+
+    #[test]
+    fn lobby_caps_count_every_seat_a_chain_holds() {
+        let mut contract = contract_with(ChainChessParameters {
+            max_open_games: 2,
+            ..ChainChessParameters::default()
+        });
+        let full = error_message(ChainChessError::LobbyLimitReached(2));
+        let first = start(&mut contract, "alice", "black");
+        start(&mut contract, "bob", "black");
+
+        // Games joined as Black count against the joiner's own lobbies...
+        let response = execute(
+            &mut contract,
+            "black",
+            Operation::CreateGame {
+                metadata: None,
+                play_vs_ai: false,
+                options: None,
+            },
+        );
+        assert_eq!(response.message, full);
+        // ...and against further joins.
+        let lobby = create(&mut contract, "carol", GameOptions::default());
+        let response = execute(
+            &mut contract,
+            "black",
+            Operation::JoinGame { game_id: lobby },
+        );
+        assert_eq!(response.message, full);
+
+        execute_ok(&mut contract, "alice", Operation::Resign { game_id: first });
+        execute_ok(
+            &mut contract,
+            "black",
+            Operation::JoinGame { game_id: lobby },
+        );
+        // Accepting a rematch needs room on both sides.
+        let rematch = || Operation::Rematch { game_id: first };
+        execute_ok(&mut contract, "alice", rematch());
+        let response = execute(&mut contract, "black", rematch());
+        assert_eq!(response.message, full);
+        execute_ok(&mut contract, "carol", Operation::Resign { game_id: lobby });
+        let game = execute_ok(&mut contract, "black", rematch());
+        assert_eq!(game.white, Some(chain("black")));
+    }
+
+    #[test]
+    fn verify_on_load_rejects_a_corrupted_game() {
+        let mut contract = contract_with(ChainChessParameters {
+            verify_on_load: true,
+            ..ChainChessParameters::default()
+        });
+        let game_id = start(&mut contract, "white", "black");
+        play(&mut contract, "white", game_id, "e2e4");
+        let mut game = stored(&contract, game_id);
+        // White's rook on h1 vanishes from the stored board.
+        game.board_fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBN1 b Qkq e3 0 1".to_string();
+        contract
+            .state
+            .active_games
+            .insert(&game_id, game)
+            .expect("game insert should succeed");
+
+        let issue = "stored board does not match the replayed moves";
+        let response = execute(&mut contract, "black", Operation::Resign { game_id });
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::InconsistentGame(
+                game_id,
+                issue.to_string()
+            ))
+        );
+        let request = format!(
+            "{{ verifyGame(gameId: {game_id}) {{ valid pliesChecked issue }} isGameConsistent(gameId: {game_id}) }}"
+        );
+        assert_eq!(
+            query(&mut contract, &request),
+            value!({
+                "verifyGame": { "valid": false, "pliesChecked": 1, "issue": issue },
+                "isGameConsistent": false,
+            })
+        );
+    }
+
+    #[test]
+    fn moves_are_refused_out_of_turn_place_or_time() {
+        let mut contract = contract();
+        let refused = |contract: &mut ChainChessContract, caller: &str, game_id, error| {
+            let operation = Operation::SubmitMove {
+                game_id,
+                uci: "e2e4".to_string(),
+                promotion: None,
+            };
+            let response = execute(contract, caller, operation);
+            assert_eq!(response.message, error_message(error));
+        };
+
+        let lobby = create(&mut contract, "white", GameOptions::default());
+        refused(
+            &mut contract,
+            "white",
+            lobby,
+            ChainChessError::MissingOpponent,
+        );
+
+        let game_id = start(&mut contract, "white", "black");
+        refused(
+            &mut contract,
+            "black",
+            game_id,
+            ChainChessError::NotYourTurn,
+        );
+        refused(
+            &mut contract,
+            "carol",
+            game_id,
+            ChainChessError::NotParticipant,
+        );
+        execute_ok(&mut contract, "black", Operation::Resign { game_id });
+        refused(
+            &mut contract,
+            "white",
+            game_id,
+            ChainChessError::AlreadyFinished,
+        );
+
+        let training = execute_ok(
+            &mut contract,
+            "white",
+            Operation::SeedTrainingGames { count: 1 },
+        );
+        refused(
+            &mut contract,
+            "white",
+            training.game_id,
+            ChainChessError::NotParticipant,
+        );
+
+        let bullet = GameOptions {
+            time_control: Some(TimeControl {
+                initial_ms: 1_000,
+                increment_ms: 0,
+            }),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "white", bullet);
+        execute_ok(&mut contract, "black", Operation::JoinGame { game_id });
+        contract.runtime.set_system_time(1_000_000.into());
+        refused(&mut contract, "white", game_id, ChainChessError::OutOfTime);
+    }
+
+    #[test]
+    fn ai_games_start_at_once_and_answer_every_move() {
+        let mut contract = contract();
+        let options = GameOptions {
+            min_rating: Some(1200),
+            ..GameOptions::default()
+        };
+        let operation = Operation::CreateGame {
+            metadata: None,
+            play_vs_ai: true,
+            options: Some(options),
+        };
+        let response = execute(&mut contract, "white", operation);
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::JoinSettingsOnAiGame)
+        );
+
+        let game_id = create_vs_ai(&mut contract, "white", GameOptions::default());
+        assert_eq!(stored(&contract, game_id).status, GameStatus::Active);
+        let response = execute(&mut contract, "black", Operation::JoinGame { game_id });
+        assert_eq!(
+            response.message,
+            error_message(ChainChessError::NotJoinable(game_id))
+        );
+
+        let operation = Operation::SubmitMove {
+            game_id,
+            uci: "e2e4".to_string(),
+            promotion: None,
+        };
+        let response = execute(&mut contract, "white", operation);
+        assert!(response.success, "{}", response.message);
+        let reply = response.ai_move.expect("the AI should reply");
+        assert_eq!(reply.played_by, PlayerColor::Black);
+        let game = stored(&contract, game_id);
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.moves[1].uci, reply.uci);
+        assert_eq!(game.turn, PlayerColor::White);
+    }
+
+    #[test]
+    fn joins_are_refused_for_own_full_or_pending_games() {
+        let mut contract = contract();
+        let join = |contract: &mut ChainChessContract, caller: &str, game_id| {
+            execute(contract, caller, Operation::JoinGame { game_id }).message
+        };
+
+        let as_black = GameOptions {
+            creator_color: Some(PlayerColor::Black),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "white", as_black);
+        let own = error_message(ChainChessError::CannotJoinOwnGame(game_id));
+        assert_eq!(join(&mut contract, "white", game_id), own);
+
+        execute_ok(&mut contract, "black", Operation::JoinGame { game_id });
+        assert_eq!(join(&mut contract, "black", game_id), own);
+        assert_eq!(
+            join(&mut contract, "carol", game_id),
+            error_message(ChainChessError::NotJoinable(game_id))
+        );
+
+        let private = GameOptions {
+            private_approval: Some(true),
+            ..GameOptions::default()
+        };
+        let game_id = create(&mut contract, "white", private);
+        execute_ok(&mut contract, "black", Operation::JoinGame { game_id });
+        assert_eq!(
+            join(&mut contract, "carol", game_id),
+            error_message(ChainChessError::NotJoinable(game_id))
+        );
+    }
+
+    #[test]
+    fn game_ids_run_out_instead_of_wrapping() {
+        let mut contract = contract();
+        contract.state.next_game_id.set(u64::MAX - 1);
+        assert_eq!(
+            create(&mut contract, "white", GameOptions::default()),
+            u64::MAX - 1
+        );
+
+        let exhausted = error_message(ChainChessError::GameIdExhausted);
+        let operation = Operation::CreateGame {
+            metadata: None,
+            play_vs_ai: false,
+            options: None,
+        };
+        assert_eq!(
+            execute(&mut contract, "black", operation).message,
+            exhausted
+        );
+        let operation = Operation::SeedTrainingGames { count: 1 };
+        assert_eq!(
+            execute(&mut contract, "black", operation).message,
+            exhausted
+        );
+        assert_eq!(*contract.state.next_game_id.get(), u64::MAX);
+    }
 }
@@ -1,6 +1,6 @@
 //! ChainChess shared types used by both the contract and the service.
 
-use async_graphql::{Enum, Request, Response, SimpleObject};
+use async_graphql::{ComplexObject, Context, Enum, InputObject, Request, Response, SimpleObject};
 use linera_sdk::{
     graphql::GraphQLMutationRoot,
     linera_base_types::{ChainId, ContractAbi, ServiceAbi, Timestamp},
@@ -8,6 +8,8 @@ use linera_sdk::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod chess_engine;
+
 /// ABI marker for the ChainChess application.
 pub struct ChainChessAbi;
 
@@ -21,17 +23,44 @@ impl ServiceAbi for ChainChessAbi {
     type QueryResponse = Response;
 }
 
+/// Settings fixed when the application is created, shared by all chains.
+/// Omitted fields take their [`Default`] values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainChessParameters {
+    /// Most unfinished games a chain may take part in at once; must be
+    /// non-zero.
+    pub max_open_games: usize,
+    /// Replay every game the contract loads and refuse to act on one whose
+    /// board disagrees with its moves. Costly; meant for tests and after
+    /// state migrations.
+    pub verify_on_load: bool,
+}
+
+impl Default for ChainChessParameters {
+    fn default() -> Self {
+        Self {
+            max_open_games: 64,
+            verify_on_load: false,
+        }
+    }
+}
+
 /// A mutation or action that can be applied to the application.
 #[derive(Debug, Serialize, Deserialize, GraphQLMutationRoot)]
 pub enum Operation {
-    /// Create a new game lobby. The caller is white by default.
+    /// Create a new game lobby. The caller is white unless
+    /// `options.creator_color` says otherwise.
     CreateGame {
         /// Optional human readable title or context for the match.
         metadata: Option<String>,
-        /// When true the black side is controlled by the built-in AI helper.
+        /// When true the built-in AI takes the seat the creator does not and
+        /// the game starts at once; such games can never be joined.
         play_vs_ai: bool,
+        /// Optional lobby settings; omitted ones take their defaults.
+        options: Option<GameOptions>,
     },
-    /// Join an existing lobby as the black player.
+    /// Join an existing lobby in whichever seat its creator left open.
     JoinGame { game_id: u64 },
     /// Submit a chess move in UCI format (e.g. "e2e4").
     SubmitMove {
@@ -41,14 +70,157 @@ pub enum Operation {
     },
     /// Resign an active game.
     Resign { game_id: u64 },
+    /// Recreate a game from a `share_code` on the caller's chain. Unfinished
+    /// imports open as an unrated lobby continuing from the last position, so
+    /// imported games never move ratings.
+    ImportShareCode {
+        code: String,
+        metadata: Option<String>,
+    },
+    /// Create up to `MAX_TRAINING_GAMES` games with the AI on both sides, kept
+    /// out of lobby listings and the leaderboard.
+    SeedTrainingGames { count: u8 },
+    /// Let the AI play a bounded number of half-moves, for both sides, in one
+    /// of the caller's active training games.
+    AutoPlay { game_id: u64 },
+    /// Admit or reject the pending joiner of a host-approval lobby.
+    ApproveJoin { game_id: u64, accept: bool },
+    /// Start a new game against the AI from the position after `at_ply`
+    /// half-moves of a finished game, to explore alternatives. Forks are
+    /// unrated.
+    ForkForAnalysis { game_id: u64, at_ply: usize },
+    /// Refuse to be paired with `chain_id` in either direction.
+    BlockPlayer { chain_id: ChainId },
+    /// Lift an earlier `BlockPlayer`.
+    UnblockPlayer { chain_id: ChainId },
+    /// Play up to `MAX_SCRIPTED_MOVES` moves for alternating sides in one go,
+    /// for setting up positions. Only allowed in the caller's own unfinished
+    /// training games and unrated open lobbies; nothing is kept if any move
+    /// is illegal.
+    PlayMoves { game_id: u64, moves: Vec<String> },
+    /// Ask for an active game to leave ratings untouched when it ends; takes
+    /// effect once both players have asked. Not available against the AI.
+    MakeUnrated { game_id: u64 },
+    /// Claim a draw in an active game whose current position has occurred
+    /// three times, or after fifty moves without a capture or pawn move.
+    ClaimDraw { game_id: u64 },
+    /// Offer the opponent a draw; moving withdraws the offer.
+    OfferDraw { game_id: u64 },
+    /// Accept the opponent's pending draw offer.
+    AcceptDraw { game_id: u64 },
+    /// Turn down the opponent's pending draw offer.
+    DeclineDraw { game_id: u64 },
+    /// Win on time once the opponent's clock has run out, or end the game
+    /// drawn instead when `result_preference` is `Draw`.
+    ClaimTimeout {
+        game_id: u64,
+        result_preference: Option<TimeoutResult>,
+    },
+    /// End an active game in which the opponent has not moved for
+    /// `INACTIVITY_ABORT_DAYS`, awarding it to the caller. Not allowed while
+    /// the caller is the side to move.
+    AbortInactive { game_id: u64 },
+    /// Withdraw a lobby nobody has joined yet; only its creator may do so.
+    CancelLobby { game_id: u64 },
+    /// Ask for a rematch of a finished game with colors swapped; the new
+    /// game starts once both players have asked.
+    Rematch { game_id: u64 },
+    /// Ask to take back your last move, along with the opponent's reply if
+    /// they already made one. The AI always agrees, but the game is then
+    /// unrated; moving withdraws the request.
+    RequestTakeback { game_id: u64 },
+    /// Grant the opponent's pending takeback request.
+    AcceptTakeback { game_id: u64 },
+    /// Store the caller's defaults for the games they create, used when
+    /// `CreateGame` leaves the corresponding option out.
+    SetPreferences {
+        default_time_control: Option<TimeControl>,
+        default_rated: bool,
+    },
+}
+
+/// A chain's defaults for the games it creates.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct Preferences {
+    /// Clock used when `CreateGame` gives no `time_control`.
+    pub default_time_control: Option<TimeControl>,
+    /// Whether new games count towards ratings.
+    pub default_rated: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            default_time_control: None,
+            default_rated: true,
+        }
+    }
+}
+
+/// Optional settings of a new game.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, InputObject)]
+pub struct GameOptions {
+    /// When true a would-be joiner waits for the creator's `ApproveJoin`.
+    pub private_approval: Option<bool>,
+    /// Lowest leaderboard rating allowed to join; unrated chains count at
+    /// the starting rating.
+    pub min_rating: Option<i32>,
+    /// Playing style of the AI in `play_vs_ai` games; balanced by default.
+    pub ai_personality: Option<Personality>,
+    /// Position to start from instead of the standard one, as FEN. Such
+    /// games are unrated.
+    pub start_fen: Option<String>,
+    /// Search depth of the AI in `play_vs_ai` games, clamped to
+    /// `MIN_AI_LEVEL..=MAX_AI_LEVEL`.
+    pub ai_level: Option<u8>,
+    /// Seat the creator takes; White by default. In `play_vs_ai` games the
+    /// AI takes the other one.
+    pub creator_color: Option<PlayerColor>,
+    /// Clock settings; games without one are untimed.
+    pub time_control: Option<TimeControl>,
+}
+
+/// Result a player asks for when claiming on the opponent's flag.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum TimeoutResult {
+    #[default]
+    Win,
+    Draw,
+}
+
+/// Starting time on each side's clock and the time added after every move.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, InputObject, SimpleObject)]
+#[graphql(name = "TimeControlSetting", input_name = "TimeControl")]
+pub struct TimeControl {
+    pub initial_ms: u64,
+    pub increment_ms: u64,
 }
 
+/// Weakest AI level, searching a single ply.
+pub const MIN_AI_LEVEL: u8 = 1;
+/// Strongest AI level; deeper searches would not fit the contract's fuel budget.
+pub const MAX_AI_LEVEL: u8 = 4;
+
+/// Days without activity after which an active game may be aborted.
+pub const INACTIVITY_ABORT_DAYS: u64 = 3;
+
+/// Most moves a single `PlayMoves` may apply.
+pub const MAX_SCRIPTED_MOVES: usize = 64;
+
+/// Most training games a single `SeedTrainingGames` may create.
+pub const MAX_TRAINING_GAMES: u8 = 10;
+
+/// Half-moves without a capture or pawn move after which a draw can be claimed.
+pub const FIFTY_MOVE_PLIES: u32 = 100;
+
 /// Public information returned after each operation.
 #[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
 pub struct ChainChessResponse {
     pub success: bool,
     pub message: String,
     pub game: Option<GameSummary>,
+    /// The AI's reply when a move against it was answered.
+    pub ai_move: Option<MoveRecord>,
 }
 
 impl ChainChessResponse {
@@ -57,6 +229,7 @@ impl ChainChessResponse {
             success: true,
             message: message.into(),
             game,
+            ai_move: None,
         }
     }
 
@@ -65,6 +238,7 @@ impl ChainChessResponse {
             success: false,
             message: message.into(),
             game: None,
+            ai_move: None,
         }
     }
 }
@@ -73,28 +247,151 @@ impl ChainChessResponse {
 #[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
 pub struct GameSummary {
     pub game_id: u64,
-    pub white: ChainId,
+    /// Bumped on every change to the game; see `game_diff`.
+    pub version: u64,
+    /// Chain that created the game, whichever seat it took.
+    pub creator: ChainId,
+    /// Empty until someone joins when the creator took Black.
+    pub white: Option<ChainId>,
     pub black: Option<ChainId>,
     pub ai_black: bool,
+    pub ai_white: bool,
+    /// Engine-vs-engine game excluded from lobby listings and the leaderboard.
+    pub training: bool,
+    /// Per-game seed from which the AI's tie-breaking seeds are derived.
+    pub ai_seed: u64,
+    pub initial_fen: String,
     pub board_fen: String,
+    /// Most recent moves; the full list is served by `move_history`.
     pub moves: Vec<MoveRecord>,
+    /// Number of earlier moves left out of `moves`.
+    pub archived_moves: u32,
+    /// Half-moves played, archived ones included.
+    pub move_count: u32,
+    /// Moves that took a piece, en passant included, archived ones too.
+    pub captures: u32,
     pub turn: PlayerColor,
     pub status: GameStatus,
     pub winner: Option<PlayerColor>,
+    /// Why the game ended, once it has.
+    pub termination: Option<TerminationReason>,
+    /// The side to move is in check. Stays true on the final position of a
+    /// game won by checkmate, so the mated king can be highlighted.
+    pub in_check: bool,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
     pub metadata: Option<String>,
+    pub private_approval: bool,
+    pub pending_joiner: Option<ChainId>,
+    /// Leaderboard rating a chain needs to join this lobby.
+    pub min_rating: Option<i32>,
+    pub ai_personality: Personality,
+    pub ai_level: u8,
+    /// Half-moves since the last capture or pawn move; a draw can be
+    /// claimed at 100.
+    pub halfmove_clock: u32,
+    pub rated: bool,
+    pub unrated_request: Option<PlayerColor>,
+    pub draw_offer: Option<PlayerColor>,
+    pub rematch_request: Option<PlayerColor>,
+    pub takeback_request: Option<PlayerColor>,
+    /// Game this one is a rematch of.
+    pub rematch_of: Option<u64>,
+    /// Rematch started from this game, if any.
+    pub rematch_id: Option<u64>,
+    /// Time left on each side's clock as of `last_move_at`; `None` in
+    /// untimed games.
+    pub white_time_ms: Option<u64>,
+    pub black_time_ms: Option<u64>,
+    pub increment_ms: u64,
+    /// Clock settings the game was created with.
+    pub time_control: Option<TimeControl>,
+    /// When the side to move's turn began, and with it their clock.
+    pub last_move_at: Option<Timestamp>,
+}
+
+impl GameSummary {
+    /// Chain sitting in the given seat, if any.
+    pub fn seat(&self, color: PlayerColor) -> Option<ChainId> {
+        match color {
+            PlayerColor::White => self.white,
+            PlayerColor::Black => self.black,
+        }
+    }
+
+    /// Seat occupied by `chain` in this game, if it is a participant.
+    pub fn color_of(&self, chain: ChainId) -> Option<PlayerColor> {
+        [PlayerColor::White, PlayerColor::Black]
+            .into_iter()
+            .find(|color| self.seat(*color) == Some(chain))
+    }
+}
+
+/// Capabilities of the deployed contract, for client feature detection.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct EngineInfo {
+    /// Name of the algorithm the built-in AI uses to pick moves.
+    pub ai_algorithm: String,
+    /// Search depth used for AI moves unless a game says otherwise.
+    pub default_depth: u8,
+    /// Starting-position variants games can be created with.
+    pub variants: Vec<String>,
+    pub clocks: bool,
+    pub threefold_repetition: bool,
+    pub fifty_move_rule: bool,
+    pub cross_chain: bool,
+    /// Names of optional features this build supports.
+    pub features: Vec<String>,
+}
+
+/// Everything a move UI needs to render the next turn of a game.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct PlayState {
+    pub board_fen: String,
+    pub turn: PlayerColor,
+    pub in_check: bool,
+    /// Legal moves in UCI notation; empty unless the game is active.
+    pub legal_moves: Vec<String>,
+    pub status: GameStatus,
+}
+
+/// What changed in a game since a client's last-known `version`. Clients
+/// keep their first `move_count - new_moves.len()` moves and append
+/// `new_moves`, which also covers moves that were taken back.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct GameDiff {
+    pub version: u64,
+    pub status: GameStatus,
+    pub turn: PlayerColor,
+    pub winner: Option<PlayerColor>,
+    pub board_fen: String,
+    /// Half-moves in the game now, archived ones included.
+    pub move_count: u32,
+    /// Moves made after the client's version, oldest first.
+    pub new_moves: Vec<MoveRecord>,
 }
 
 /// Lightweight leaderboard entry.
 #[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct PlayerStats {
     pub chain_id: ChainId,
     pub wins: u32,
     pub losses: u32,
     pub draws: u32,
     pub games_played: u32,
+    /// Raw rating, never reduced by inactivity.
     pub rating: i32,
+    /// When the player last finished a game.
+    pub last_active: Timestamp,
+    /// Fewer than `PROVISIONAL_GAMES` games played, so the rating still
+    /// moves quickly and may be far from the player's strength.
+    pub provisional: bool,
+    /// Consecutive wins when positive, consecutive losses when negative;
+    /// a draw resets it to zero.
+    pub current_streak: i32,
+    /// Longest run of consecutive wins.
+    pub best_win_streak: u32,
 }
 
 impl PlayerStats {
@@ -105,9 +402,151 @@ impl PlayerStats {
             losses: 0,
             draws: 0,
             games_played: 0,
-            rating: 0,
+            rating: STARTING_RATING,
+            last_active: Timestamp::from(0),
+            provisional: true,
+            current_streak: 0,
+            best_win_streak: 0,
+        }
+    }
+
+    /// Counts a finished game played as `color`, where `winner` is `None` for
+    /// a draw, and updates the streaks. The rating is left to the caller.
+    pub fn record_outcome(&mut self, color: PlayerColor, winner: Option<PlayerColor>) {
+        match winner {
+            Some(winner) if winner == color => {
+                self.wins += 1;
+                self.current_streak = self.current_streak.max(0) + 1;
+                self.best_win_streak = self.best_win_streak.max(self.current_streak.unsigned_abs());
+            }
+            Some(_) => {
+                self.losses += 1;
+                self.current_streak = self.current_streak.min(0) - 1;
+            }
+            None => {
+                self.draws += 1;
+                self.current_streak = 0;
+            }
         }
+        self.games_played += 1;
+        self.provisional = self.games_played < PROVISIONAL_GAMES;
     }
+
+    /// Rating after inactivity decay as of `now`, see [`RatingDecay`].
+    pub fn rating_at(&self, now: Timestamp, decay: &RatingDecay) -> i32 {
+        let idle_days = now.delta_since(self.last_active).as_micros() / MICROS_PER_DAY;
+        let decayed_days = idle_days.saturating_sub(decay.grace_days);
+        let penalty = decayed_days
+            .saturating_mul(decay.points_per_day)
+            .min(decay.max_penalty);
+        self.rating - penalty as i32
+    }
+}
+
+#[ComplexObject]
+impl PlayerStats {
+    /// Rating with the default inactivity decay applied as of the query time.
+    async fn decayed_rating(&self, ctx: &Context<'_>) -> i32 {
+        let now = ctx
+            .data_opt::<Timestamp>()
+            .copied()
+            .unwrap_or(self.last_active);
+        self.rating_at(now, &RatingDecay::DEFAULT)
+    }
+}
+
+/// Elo expected score of a player rated `rating` against one rated
+/// `opponent_rating`, from 0.0 to 1.0.
+pub fn expected_score(rating: i32, opponent_rating: i32) -> f32 {
+    let diff = (opponent_rating - rating) as f32;
+    1.0 / (1.0 + 10f32.powf(diff / 400.0))
+}
+
+/// Rating change of a player rated `rating` who scored `score` (1.0 for a
+/// win, 0.5 for a draw, 0.0 for a loss) against `opponent_rating`, under
+/// Elo with the given K-factor.
+pub fn elo_delta(rating: i32, opponent_rating: i32, score: f32, k_factor: f32) -> i32 {
+    (k_factor * (score - expected_score(rating, opponent_rating))).round() as i32
+}
+
+/// Rating of a chain that has not finished a game yet.
+pub const STARTING_RATING: i32 = 1200;
+
+/// Games a player's rating stays provisional for.
+pub const PROVISIONAL_GAMES: u32 = 20;
+
+const MICROS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000;
+
+/// Linear rating decay for inactive players.
+///
+/// After `grace_days` full days without a finished game, the effective rating
+/// drops by `points_per_day` per further idle day, by at most `max_penalty`.
+/// The raw rating is left untouched, so one new result restores it.
+#[derive(Clone, Copy, Debug)]
+pub struct RatingDecay {
+    pub grace_days: u64,
+    pub points_per_day: u64,
+    pub max_penalty: u64,
+}
+
+impl RatingDecay {
+    pub const DEFAULT: Self = Self {
+        grace_days: 30,
+        points_per_day: 2,
+        max_penalty: 200,
+    };
+}
+
+/// One way of completing a pawn promotion.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct PromotionOption {
+    /// Full UCI move including the promotion letter, e.g. "e7e8n".
+    pub uci: String,
+    /// Lowercase promotion letter: "q", "r", "b" or "n".
+    pub piece: String,
+    pub gives_check: bool,
+    pub gives_mate: bool,
+    pub stalemates: bool,
+}
+
+/// Promotion choices for a pawn move, strongest preference first.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct PromotionOptions {
+    pub options: Vec<PromotionOption>,
+    /// UCI of the strongest promotion: a mating one, otherwise the most
+    /// valuable piece that does not stalemate.
+    pub best: String,
+}
+
+/// How often a continuation was played after a move prefix, and how it went.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct MoveStat {
+    /// Moves played after the prefix, in UCI notation.
+    pub line: Vec<String>,
+    pub games: u32,
+    pub white_wins: u32,
+    pub black_wins: u32,
+    pub draws: u32,
+    /// Share of `games` won by White, from 0.0 to 1.0.
+    pub white_win_rate: f64,
+}
+
+/// A notable finished game in the chain's record book.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct GameRecord {
+    pub game_id: u64,
+    /// Number of half-moves played.
+    pub plies: u32,
+    pub winner: Option<PlayerColor>,
+}
+
+/// Records derived from the finished games stored on a chain.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+pub struct ChainRecords {
+    /// The finished game with the most half-moves.
+    pub longest_game: Option<GameRecord>,
+    /// The checkmate win reached in the fewest half-moves.
+    pub fastest_win: Option<GameRecord>,
 }
 
 /// Stored move plus metadata.
@@ -117,6 +556,117 @@ pub struct MoveRecord {
     pub san: Option<String>,
     pub played_by: PlayerColor,
     pub played_at: Timestamp,
+    /// Search inputs for moves chosen by the built-in AI.
+    pub ai_trace: Option<AiMoveTrace>,
+    /// Piece taken by this move, if any.
+    pub captured: Option<PieceKind>,
+    /// Game version that added this move.
+    pub version: u64,
+}
+
+/// Inputs to `chess_engine::pick_ai_move` for an AI move, so anyone can
+/// replay the search from the prior position and confirm the same reply.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct AiMoveTrace {
+    pub seed: u64,
+    pub depth: u8,
+    pub personality: Personality,
+}
+
+/// Playing style of the built-in AI, applied as extra centipawn weights in
+/// its search evaluation.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum Personality {
+    /// Values checks and attacks on the enemy king.
+    Aggressive,
+    /// Values keeping the squares around its own king unattacked.
+    Defensive,
+    #[default]
+    Balanced,
+}
+
+/// Chess piece types, as surfaced to clients.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+/// Number of pieces of each type captured.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+pub struct CaptureCounts {
+    pub pawns: u32,
+    pub knights: u32,
+    pub bishops: u32,
+    pub rooks: u32,
+    pub queens: u32,
+}
+
+impl CaptureCounts {
+    pub fn add(&mut self, piece: PieceKind) {
+        match piece {
+            PieceKind::Pawn => self.pawns += 1,
+            PieceKind::Knight => self.knights += 1,
+            PieceKind::Bishop => self.bishops += 1,
+            PieceKind::Rook => self.rooks += 1,
+            PieceKind::Queen => self.queens += 1,
+            // Kings are never captured.
+            PieceKind::King => {}
+        }
+    }
+}
+
+/// Outcome of replaying a stored game to audit it.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct GameVerification {
+    pub valid: bool,
+    /// Half-moves replayed before stopping.
+    pub plies_checked: u32,
+    /// First inconsistency found, if any.
+    pub issue: Option<String>,
+}
+
+/// Number of a player's games in each status.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+pub struct StatusCounts {
+    pub lobby: u32,
+    pub active: u32,
+    pub finished: u32,
+}
+
+/// Finished draws on a chain, by how they came about.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+pub struct DrawBreakdown {
+    pub repetition: u32,
+    pub fifty_move: u32,
+    pub stalemate: u32,
+    pub insufficient_material: u32,
+    pub agreement: u32,
+    /// Drawn on time or after abandonment.
+    pub other: u32,
+}
+
+/// Results between two chains, from the first one's point of view.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+pub struct HeadToHead {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl HeadToHead {
+    /// The same record from the other chain's point of view.
+    pub fn flipped(&self) -> Self {
+        Self {
+            wins: self.losses,
+            losses: self.wins,
+            draws: self.draws,
+        }
+    }
 }
 
 /// Indicates whose turn it is or who played a move.
@@ -135,6 +685,22 @@ impl PlayerColor {
     }
 }
 
+/// Why a finished game ended.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Enum)]
+pub enum TerminationReason {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    /// Both sides agreed a draw, including the AI accepting or proposing one.
+    Agreement,
+    Timeout,
+    /// Ended by `AbortInactive` after a player stopped moving.
+    Abandoned,
+    FiftyMove,
+    Threefold,
+    InsufficientMaterial,
+}
+
 /// High level status for a match.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Enum)]
 pub enum GameStatus {
@@ -143,6 +709,45 @@ pub enum GameStatus {
     Finished,
 }
 
+/// Stream the contract emits its [`GameEvent`]s on.
+pub const GAME_EVENTS_STREAM: &str = "games";
+
+/// Event emitted on `GAME_EVENTS_STREAM` so indexers can follow games
+/// without polling.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GameEvent {
+    GameCreated {
+        game_id: u64,
+        creator: ChainId,
+        status: GameStatus,
+    },
+    GameJoined {
+        game_id: u64,
+        player: ChainId,
+        color: PlayerColor,
+    },
+    MovePlayed {
+        game_id: u64,
+        uci: String,
+        played_by: PlayerColor,
+        /// Status of the game after the move.
+        status: GameStatus,
+    },
+    GameFinished {
+        game_id: u64,
+        winner: Option<PlayerColor>,
+        reason: TerminationReason,
+    },
+    /// An accepted takeback undid the last `plies` half-moves.
+    MovesTakenBack { game_id: u64, plies: usize },
+    /// The creator cancelled the lobby and the game was deleted.
+    LobbyCancelled { game_id: u64 },
+    /// The creator of a private lobby turned down `player`'s join request.
+    JoinRejected { game_id: u64, player: ChainId },
+    /// The game no longer moves ratings.
+    GameUnrated { game_id: u64 },
+}
+
 /// Domain errors bubbled up to the caller.
 #[derive(Debug, Error, Serialize, Deserialize)]
 pub enum ChainChessError {
@@ -156,12 +761,74 @@ pub enum ChainChessError {
     AlreadyFinished,
     #[error("game is still waiting for an opponent")]
     MissingOpponent,
+    #[error("malformed UCI move: {0:?}")]
+    MalformedUci(String),
     #[error("invalid move: {0}")]
     InvalidMove(String),
     #[error("you are not a participant in this game")]
     NotParticipant,
-    #[error("cannot create more than 64 concurrent games per chain")]
-    LobbyLimitReached,
+    #[error("only the game creator can do that")]
+    NotHost,
+    #[error("game {0} has no pending join request")]
+    NoPendingJoin(u64),
+    #[error("training games must be seeded {MAX_TRAINING_GAMES} at a time or fewer")]
+    InvalidTrainingCount,
+    #[error("invalid FEN: {0}")]
+    InvalidFen(String),
+    #[error("share code is malformed or contains illegal moves")]
+    InvalidShareCode,
+    #[error("a rating of at least {0} is required to join this game")]
+    RatingTooLow(i32),
+    #[error("game {0} is still in progress")]
+    NotFinished(u64),
+    #[error("game {0} has already started")]
+    AlreadyStarted(u64),
+    #[error("ply {0} is not a playable position of the game")]
+    InvalidPly(usize),
+    #[error("you cannot block yourself")]
+    CannotBlockSelf,
+    #[error("one of the players has blocked the other")]
+    PlayerBlocked,
+    #[error("moves can only be scripted in your own training games and unrated open lobbies")]
+    NotScriptable,
+    #[error("at most {MAX_SCRIPTED_MOVES} moves can be played at once")]
+    TooManyMoves,
+    #[error("no draw can be claimed in this position")]
+    DrawClaimRejected,
+    #[error("there is no draw offer from your opponent")]
+    NoDrawOffer,
+    #[error("game {0} is not timed")]
+    Untimed(u64),
+    #[error("your opponent still has time on the clock")]
+    ClockNotExpired,
+    #[error("the game has not been inactive for {INACTIVITY_ABORT_DAYS} days yet")]
+    NotYetAbortable,
+    #[error("a rematch needs two human players")]
+    NoRematchOpponent,
+    #[error("cannot create more than {0} concurrent games per chain")]
+    LobbyLimitReached(usize),
+    #[error("this chain has run out of game ids")]
+    GameIdExhausted,
+    #[error("you already hold a seat in game {0}")]
+    CannotJoinOwnGame(u64),
+    #[error("you have no move to take back")]
+    NoMoveToTakeBack,
+    #[error("there is no takeback request from your opponent")]
+    NoTakebackRequest,
+    #[error("AI games start at once and take no join settings")]
+    JoinSettingsOnAiGame,
+    #[error("game {0} does not match its move history: {1}")]
+    InconsistentGame(u64, String),
+    #[error("game {0} is not one of your training games")]
+    NotTrainingGame(u64),
+    #[error("your time has run out")]
+    OutOfTime,
+    #[error("this game has already been rematched as game {0}")]
+    AlreadyRematched(u64),
+    #[error("only the player waiting for a move can abort an inactive game")]
+    NotAbortableOnYourMove,
+    #[error("games against the AI cannot be made unrated")]
+    AiGameStaysRated,
 }
 
 impl ChainChessResponse {
@@ -169,3 +836,76 @@ impl ChainChessResponse {
         Self::err(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use linera_sdk::linera_base_types::CryptoHash;
+
+    use super::*;
+
+    fn stats_last_active_on(active_day: u64) -> PlayerStats {
+        let mut stats = PlayerStats::new(ChainId(CryptoHash::test_hash("player")));
+        stats.rating = 1500;
+        stats.last_active = day(active_day);
+        stats
+    }
+
+    fn day(day: u64) -> Timestamp {
+        Timestamp::from(day * MICROS_PER_DAY)
+    }
+
+    #[test]
+    fn rating_decays_after_the_grace_period_down_to_the_cap() {
+        let decay = RatingDecay::DEFAULT;
+        let stats = stats_last_active_on(10);
+        assert_eq!(stats.rating_at(day(10), &decay), 1500);
+        assert_eq!(stats.rating_at(day(40), &decay), 1500);
+        assert_eq!(stats.rating_at(day(41), &decay), 1498);
+        assert_eq!(stats.rating_at(day(50), &decay), 1480);
+        // Part of a day does not count.
+        let almost = Timestamp::from(51 * MICROS_PER_DAY - 1);
+        assert_eq!(stats.rating_at(almost, &decay), 1480);
+        assert_eq!(stats.rating_at(day(140), &decay), 1300);
+        assert_eq!(stats.rating_at(day(1_000), &decay), 1300);
+        assert_eq!(stats.rating, 1500);
+    }
+
+    #[test]
+    fn elo_delta_scales_with_the_k_factor() {
+        // Provisional and established K-factors, as used by the contract.
+        for (k_factor, even) in [(40.0, 20), (20.0, 10)] {
+            assert_eq!(elo_delta(1200, 1200, 1.0, k_factor), even);
+            assert_eq!(elo_delta(1200, 1200, 0.5, k_factor), 0);
+            assert_eq!(elo_delta(1200, 1200, 0.0, k_factor), -even);
+        }
+        // An upset against a player rated 400 higher: expected score 1/11.
+        assert_eq!(elo_delta(1200, 1600, 1.0, 40.0), 36);
+        assert_eq!(elo_delta(1200, 1600, 1.0, 20.0), 18);
+        assert_eq!(elo_delta(1600, 1200, 0.0, 40.0), -36);
+        assert_eq!(elo_delta(1600, 1200, 0.0, 20.0), -18);
+    }
+
+    #[test]
+    fn record_outcome_tracks_streaks() {
+        let (white, black) = (PlayerColor::White, PlayerColor::Black);
+        let mut stats = stats_last_active_on(0);
+        stats.record_outcome(white, Some(white));
+        stats.record_outcome(black, Some(black));
+        assert_eq!(stats.wins, 2);
+        assert_eq!((stats.current_streak, stats.best_win_streak), (2, 2));
+        stats.record_outcome(white, Some(black));
+        assert_eq!((stats.losses, stats.current_streak), (1, -1));
+        stats.record_outcome(black, Some(white));
+        assert_eq!(stats.current_streak, -2);
+        stats.record_outcome(white, Some(white));
+        assert_eq!((stats.current_streak, stats.best_win_streak), (1, 2));
+        stats.record_outcome(white, None);
+        assert_eq!((stats.draws, stats.current_streak), (1, 0));
+        stats.record_outcome(white, Some(black));
+        stats.record_outcome(white, None);
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.games_played, 8);
+        assert!(stats.provisional);
+        assert_eq!(stats.rating, 1500);
+    }
+}
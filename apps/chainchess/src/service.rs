@@ -4,8 +4,11 @@ mod state;
 
 use std::sync::Arc;
 
-use async_graphql::{EmptySubscription, Request, Response, Schema};
-use chainchess::{ChainChessAbi, Operation};
+use async_graphql::{
+    futures_util::{stream, Stream, StreamExt},
+    Request, Response, Schema, Subscription,
+};
+use chainchess::{ChainChessAbi, ChainChessError, ChainChessParameters, GameSummary, Operation};
 use linera_sdk::{
     graphql::GraphQLMutationRoot as _, linera_base_types::WithServiceAbi, views::View, Service,
     ServiceRuntime,
@@ -24,7 +27,7 @@ impl WithServiceAbi for ChainChessService {
 }
 
 impl Service for ChainChessService {
-    type Parameters = ();
+    type Parameters = ChainChessParameters;
 
     async fn new(runtime: ServiceRuntime<Self>) -> Self {
         let state = ChainChessState::load(runtime.root_view_storage_context())
@@ -40,9 +43,44 @@ impl Service for ChainChessService {
         let schema = Schema::build(
             self.state.clone(),
             Operation::mutation_root(self.runtime.clone()),
-            EmptySubscription,
+            SubscriptionRoot {
+                state: self.state.clone(),
+            },
         )
+        .data(self.runtime.system_time())
+        .data(self.runtime.application_parameters())
         .finish();
-        schema.execute(request).await
+        // A service answers each request once, so a subscription yields its
+        // first item; queries and mutations produce a single response anyway.
+        let mut responses = schema.execute_stream(request);
+        responses.next().await.unwrap_or_default()
+    }
+}
+
+/// Subscriptions for spectators following live games.
+///
+/// A service only sees the chain as of the latest block and answers each
+/// request once, so updates are per block, not per move: clients re-run a
+/// subscription whenever the node's `notifications` subscription reports a
+/// new block on this chain, and a block holding several moves shows up as a
+/// single update.
+struct SubscriptionRoot {
+    state: Arc<ChainChessState>,
+}
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// The game as of the latest block.
+    async fn game_updated(
+        &self,
+        game_id: u64,
+    ) -> async_graphql::Result<impl Stream<Item = GameSummary>> {
+        let game = self
+            .state
+            .active_games
+            .get(&game_id)
+            .await?
+            .ok_or_else(|| ChainChessError::GameNotFound(game_id).to_string())?;
+        Ok(stream::iter([game.to_summary()]))
     }
 }
@@ -1,13 +1,35 @@
-use std::cmp::Reverse;
+use std::{cmp::Reverse, collections::BTreeMap};
 
-use async_graphql::ComplexObject;
+use async_graphql::{ComplexObject, Context};
 use linera_sdk::{
     linera_base_types::{ChainId, Timestamp},
     views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext},
 };
 use serde::{Deserialize, Serialize};
 
-use chainchess::{GameStatus, GameSummary, MoveRecord, PlayerColor, PlayerStats};
+use chainchess::{
+    chess_engine::{
+        apply_uci_move, blunder_warning, board_unicode, encode_pgn, encode_share_code, engine_info,
+        eval_history, evaluate_fen, explain_illegal, forced_mate, hanging_pieces, in_check,
+        is_checkmate, legal_moves, move_counters, pgn_date, principal_variation, promotion_options,
+        replay_fen, side_to_move, validate_fen, MatchResult, MoveComputation,
+    },
+    expected_score, AiMoveTrace, CaptureCounts, ChainChessError, ChainChessParameters,
+    ChainRecords, DrawBreakdown, EngineInfo, GameDiff, GameRecord, GameStatus, GameSummary,
+    GameVerification, HeadToHead, MoveRecord, MoveStat, Personality, PlayState, PlayerColor,
+    PlayerStats, Preferences, PromotionOptions, RatingDecay, StatusCounts, TerminationReason,
+    TimeControl, FIFTY_MOVE_PLIES,
+};
+
+/// Most games aggregated by a single career-statistics query.
+const MAX_CAREER_SCAN_GAMES: usize = 500;
+
+/// Games returned by one `open_lobbies` or `player_games` query unless a
+/// `limit` is given.
+const DEFAULT_GAMES_PAGE: usize = 100;
+
+/// Deepest continuation `move_tree` expands past the prefix.
+const MAX_MOVE_TREE_DEPTH: u8 = 4;
 
 /// Application level state that lives on each microchain.
 #[derive(RootView, async_graphql::SimpleObject)]
@@ -20,62 +42,1028 @@ pub struct ChainChessState {
     pub active_games: MapView<u64, StoredGame>,
     /// Basic Elo-style scores per participant.
     pub leaderboard: MapView<ChainId, PlayerStats>,
+    /// Chains each player refuses to be paired with.
+    pub blocklist: MapView<ChainId, Vec<ChainId>>,
+    /// Oldest moves of long games, moved out of `StoredGame::moves` to keep
+    /// game records small; `move_history` stitches them back together.
+    #[graphql(skip)]
+    pub move_history_overflow: MapView<u64, Vec<MoveRecord>>,
+    /// Results between pairs of chains, keyed by the lower chain id first and
+    /// seen from that chain's side; read through `head_to_head`.
+    #[graphql(skip)]
+    pub head_to_head_records: MapView<(ChainId, ChainId), HeadToHead>,
+    /// Games hosted on other chains that this chain plays in, keyed by host
+    /// chain and the game's id there; kept current by `GameUpdate` messages.
+    #[graphql(skip)]
+    pub remote_games: MapView<(ChainId, u64), StoredGame>,
+    /// Game defaults set by each chain with `SetPreferences`; read through
+    /// `preferences`.
+    #[graphql(skip)]
+    pub player_preferences: MapView<ChainId, Preferences>,
 }
 
 /// Internal representation kept inside storage.
 #[derive(Clone, Debug, Serialize, Deserialize, async_graphql::SimpleObject)]
 pub struct StoredGame {
     pub game_id: u64,
-    pub white: ChainId,
+    /// Bumped each time the game is saved.
+    pub version: u64,
+    /// Chain that created the game; set once at creation.
+    pub creator: ChainId,
+    pub white: Option<ChainId>,
     pub black: Option<ChainId>,
     pub ai_black: bool,
+    pub ai_white: bool,
+    pub training: bool,
+    pub ai_seed: u64,
+    /// Position the game started from; replays begin here.
+    pub initial_fen: String,
     pub board_fen: String,
+    /// Most recent moves; the first `archived_moves` live in the overflow.
     pub moves: Vec<MoveRecord>,
+    pub archived_moves: u32,
+    /// Captures among the archived moves.
+    pub archived_captures: u32,
     pub turn: PlayerColor,
     pub status: GameStatus,
     pub winner: Option<PlayerColor>,
+    pub termination: Option<TerminationReason>,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
     pub metadata: Option<String>,
+    /// Joins must be approved by the creator before the game starts.
+    pub private_approval: bool,
+    /// Chain waiting for the creator's approval in a private lobby.
+    pub pending_joiner: Option<ChainId>,
+    /// Leaderboard rating a chain needs to join this lobby.
+    pub min_rating: Option<i32>,
+    /// Style the AI plays with in this game.
+    pub ai_personality: Personality,
+    /// Plies the AI searches for each of its replies in this game.
+    pub ai_level: u8,
+    /// Half-moves since the last capture or pawn move.
+    pub halfmove_clock: u32,
+    /// Whether the result moves ratings; cleared by a mutual `MakeUnrated`.
+    pub rated: bool,
+    /// Side that has asked to make the game unrated, awaiting the other.
+    pub unrated_request: Option<PlayerColor>,
+    /// Side with a pending draw offer.
+    pub draw_offer: Option<PlayerColor>,
+    /// Side that has asked for a rematch of this finished game.
+    pub rematch_request: Option<PlayerColor>,
+    /// Side waiting for the opponent to grant a takeback.
+    pub takeback_request: Option<PlayerColor>,
+    /// Finished game this one is a rematch of.
+    pub rematch_of: Option<u64>,
+    /// Rematch started from this finished game, once there is one.
+    pub rematch_id: Option<u64>,
+    /// Time left on each side's clock as of `last_move_at`; `None` in
+    /// untimed games.
+    pub white_time_ms: Option<u64>,
+    pub black_time_ms: Option<u64>,
+    /// Time added to the mover's clock after each move.
+    pub increment_ms: u64,
+    /// Clock settings the game was created with; `None` in untimed games.
+    pub time_control: Option<TimeControl>,
+    /// When the side to move's turn began, and with it their clock; set once
+    /// the game starts.
+    pub last_move_at: Option<Timestamp>,
 }
 
 impl StoredGame {
     pub fn to_summary(&self) -> GameSummary {
         GameSummary {
             game_id: self.game_id,
+            version: self.version,
+            creator: self.creator,
             white: self.white,
             black: self.black,
             ai_black: self.ai_black,
+            ai_white: self.ai_white,
+            training: self.training,
+            ai_seed: self.ai_seed,
+            initial_fen: self.initial_fen.clone(),
             board_fen: self.board_fen.clone(),
             moves: self.moves.clone(),
+            archived_moves: self.archived_moves,
+            move_count: self.archived_moves + self.moves.len() as u32,
+            captures: self.archived_captures
+                + self
+                    .moves
+                    .iter()
+                    .filter(|record| record.captured.is_some())
+                    .count() as u32,
             turn: self.turn,
             status: self.status,
             winner: self.winner,
+            termination: self.termination,
+            in_check: in_check(&self.board_fen),
             created_at: self.created_at,
             updated_at: self.updated_at,
             metadata: self.metadata.clone(),
+            private_approval: self.private_approval,
+            pending_joiner: self.pending_joiner,
+            min_rating: self.min_rating,
+            ai_personality: self.ai_personality,
+            ai_level: self.ai_level,
+            halfmove_clock: self.halfmove_clock,
+            rated: self.rated,
+            unrated_request: self.unrated_request,
+            draw_offer: self.draw_offer,
+            rematch_request: self.rematch_request,
+            takeback_request: self.takeback_request,
+            rematch_of: self.rematch_of,
+            rematch_id: self.rematch_id,
+            white_time_ms: self.white_time_ms,
+            black_time_ms: self.black_time_ms,
+            increment_ms: self.increment_ms,
+            time_control: self.time_control,
+            last_move_at: self.last_move_at,
+        }
+    }
+
+    /// Applies a computed move for the side to move and returns its result, if terminal.
+    /// In a running timed game the time since `last_move_at` comes off the
+    /// mover's clock and the increment is added.
+    ///
+    /// Finishing the game (and updating stats) is left to the caller.
+    pub fn record_move(
+        &mut self,
+        outcome: MoveComputation,
+        played_at: Timestamp,
+        ai_trace: Option<AiMoveTrace>,
+    ) -> Option<MatchResult> {
+        let played_by = self.turn;
+        if let Some(started) = self.last_move_at {
+            let elapsed_ms = played_at.delta_since(started).as_micros() / 1_000;
+            let clock = match played_by {
+                PlayerColor::White => &mut self.white_time_ms,
+                PlayerColor::Black => &mut self.black_time_ms,
+            };
+            if let Some(remaining) = clock {
+                *remaining = remaining.saturating_sub(elapsed_ms) + self.increment_ms;
+            }
+            self.last_move_at = Some(played_at);
+        }
+        self.halfmove_clock = move_counters(&outcome.fen).0;
+        self.draw_offer = None;
+        self.takeback_request = None;
+        self.board_fen = outcome.fen;
+        self.turn = played_by.other();
+        self.moves.push(MoveRecord {
+            uci: outcome.uci,
+            san: outcome.san,
+            played_by,
+            played_at,
+            ai_trace,
+            captured: outcome.captured,
+            // The save that follows this move produces the next version.
+            version: self.version + 1,
+        });
+        self.updated_at = played_at;
+        outcome.result
+    }
+
+    /// Marks the game finished with the given result.
+    pub fn finish(&mut self, result: MatchResult, at: Timestamp) {
+        self.status = GameStatus::Finished;
+        self.winner = result.winner();
+        self.termination = Some(result.reason());
+        self.updated_at = at;
+    }
+
+    /// Chain sitting in the given seat, if any.
+    pub fn seat(&self, color: PlayerColor) -> Option<ChainId> {
+        match color {
+            PlayerColor::White => self.white,
+            PlayerColor::Black => self.black,
+        }
+    }
+
+    /// Whether the built-in AI plays `color`.
+    pub fn is_ai(&self, color: PlayerColor) -> bool {
+        match color {
+            PlayerColor::White => self.ai_white,
+            PlayerColor::Black => self.ai_black,
+        }
+    }
+
+    /// Seat occupied by `chain` in this game, if it is a participant.
+    pub fn color_of(&self, chain: ChainId) -> Option<PlayerColor> {
+        [PlayerColor::White, PlayerColor::Black]
+            .into_iter()
+            .find(|color| self.seat(*color) == Some(chain))
+    }
+
+    /// Board FEN after the first `ply` half-moves of the game's full
+    /// `history`; `None` past the last move.
+    pub fn board_at_ply(&self, history: &[MoveRecord], ply: usize) -> Option<String> {
+        replay_fen(
+            &self.initial_fen,
+            history.get(..ply)?.iter().map(|record| record.uci.as_str()),
+        )
+    }
+
+    /// Replays `history` (the game's full move list) from `initial_fen` and
+    /// compares it with the stored record, returning the number of plies
+    /// replayed and the first inconsistency found.
+    pub fn audit(&self, history: &[MoveRecord]) -> (u32, Option<String>) {
+        let mut fen = self.initial_fen.clone();
+        let mut to_move = side_to_move(&fen);
+        let mut result = None;
+        for (ply, record) in history.iter().enumerate() {
+            let ply = ply as u32;
+            if result.is_some() {
+                return (
+                    ply,
+                    Some(format!("move {} follows the end of the game", ply + 1)),
+                );
+            }
+            if Some(record.played_by) != to_move {
+                return (
+                    ply,
+                    Some(format!("move {} was played out of turn", ply + 1)),
+                );
+            }
+            let outcome = match apply_uci_move(&fen, &record.uci, None) {
+                Ok(outcome) if outcome.uci == record.uci => outcome,
+                _ => {
+                    return (
+                        ply,
+                        Some(format!("move {} ({}) is illegal", ply + 1, record.uci)),
+                    )
+                }
+            };
+            if record.san.is_some() && record.san != outcome.san {
+                return (
+                    ply,
+                    Some(format!(
+                        "move {} ({}) is recorded with the wrong SAN",
+                        ply + 1,
+                        record.uci
+                    )),
+                );
+            }
+            fen = outcome.fen;
+            to_move = to_move.map(PlayerColor::other);
+            result = outcome.result;
+        }
+        let plies = history.len() as u32;
+        let issue = if fen != self.board_fen {
+            Some("stored board does not match the replayed moves".to_string())
+        } else if Some(self.turn) != to_move {
+            Some("stored turn does not match the replayed moves".to_string())
+        } else {
+            match result {
+                Some(_) if self.status != GameStatus::Finished => {
+                    Some("game ended on the board but is not finished".to_string())
+                }
+                Some(MatchResult::Winner(winner, _)) if self.winner != Some(winner) => {
+                    Some("stored winner does not match the checkmate".to_string())
+                }
+                Some(MatchResult::Draw(_)) if self.winner.is_some() => {
+                    Some("game drawn on the board has a winner".to_string())
+                }
+                _ => None,
+            }
+        };
+        (plies, issue)
+    }
+
+    /// Checks that replaying `history` (the game's full move list) from
+    /// `initial_fen` reproduces the stored board, turn and result.
+    pub fn verify(&self, history: &[MoveRecord]) -> Result<(), ChainChessError> {
+        match self.audit(history) {
+            (_, Some(issue)) => Err(ChainChessError::InconsistentGame(self.game_id, issue)),
+            (_, None) => Ok(()),
+        }
+    }
+
+    /// Standard result token: "1-0", "0-1", "1/2-1/2", or "*" while in progress.
+    pub fn result_token(&self) -> &'static str {
+        match (self.status, self.winner) {
+            (GameStatus::Finished, Some(PlayerColor::White)) => "1-0",
+            (GameStatus::Finished, Some(PlayerColor::Black)) => "0-1",
+            (GameStatus::Finished, None) => "1/2-1/2",
+            _ => "*",
         }
     }
 }
 
-#[ComplexObject]
 impl ChainChessState {
-    /// Returns all games stored locally; useful for dashboards and explorers.
-    pub async fn games(&self) -> Vec<GameSummary> {
+    /// Scans all stored games, keeping those accepted by `filter`, sorted by id.
+    /// Training games are skipped; they are only listed by `training_games`.
+    async fn collect_games(&self, filter: impl Fn(&StoredGame) -> bool) -> Vec<GameSummary> {
+        self.scan_games(|game| !game.training && filter(game)).await
+    }
+
+    /// A game's archived moves followed by its `recent` ones, oldest first.
+    pub async fn full_history(
+        &self,
+        game_id: u64,
+        archived_moves: u32,
+        recent: &[MoveRecord],
+    ) -> Vec<MoveRecord> {
+        if archived_moves == 0 {
+            return recent.to_vec();
+        }
+        let mut history = self
+            .move_history_overflow
+            .get(&game_id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        history.extend_from_slice(recent);
+        history
+    }
+
+    /// Current leaderboard rating, or the starting rating for unknown chains.
+    pub async fn rating_of(&self, chain_id: ChainId) -> i32 {
+        match self.leaderboard.get(&chain_id).await {
+            Ok(Some(stats)) => stats.rating,
+            _ => PlayerStats::new(chain_id).rating,
+        }
+    }
+
+    async fn scan_games(&self, filter: impl Fn(&StoredGame) -> bool) -> Vec<GameSummary> {
         let mut results = Vec::new();
         if let Ok(indices) = self.active_games.indices().await {
             for id in indices {
                 if let Ok(Some(game)) = self.active_games.get(&id).await {
-                    results.push(game.to_summary());
+                    if filter(&game) {
+                        results.push(game.to_summary());
+                    }
                 }
             }
         }
         results.sort_by_key(|g| g.game_id);
         results
     }
+}
+
+#[ComplexObject]
+impl ChainChessState {
+    /// Returns the games stored locally, optionally only those with the given
+    /// `status` or with `player` in either seat; useful for dashboards and
+    /// explorers. Pages of `limit` games start `offset` games into the list
+    /// ordered by id; without a `limit` every game from `offset` on is
+    /// returned.
+    pub async fn games(
+        &self,
+        status: Option<GameStatus>,
+        player: Option<ChainId>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<GameSummary> {
+        self.collect_games(|game| {
+            status.is_none_or(|status| game.status == status)
+                && player.is_none_or(|player| game.color_of(player).is_some())
+        })
+        .await
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+    }
+
+    /// Most unfinished games a chain may take part in at once, as set in the
+    /// application parameters.
+    pub async fn max_open_games(&self, ctx: &Context<'_>) -> usize {
+        ctx.data_opt::<ChainChessParameters>()
+            .cloned()
+            .unwrap_or_default()
+            .max_open_games
+    }
+
+    /// A single game by id, or `None` if there is no such game.
+    pub async fn game(&self, id: u64) -> Option<GameSummary> {
+        let game = self.active_games.get(&id).await.ok()??;
+        Some(game.to_summary())
+    }
+
+    /// Games hosted on other chains in which this chain holds a seat, as last
+    /// reported by their host chains.
+    pub async fn remote_games(&self) -> Vec<GameSummary> {
+        let mut games = Vec::new();
+        if let Ok(keys) = self.remote_games.indices().await {
+            for key in keys {
+                if let Ok(Some(game)) = self.remote_games.get(&key).await {
+                    games.push(game.to_summary());
+                }
+            }
+        }
+        games
+    }
+
+    /// Lobbies a human can join right now, oldest first: an empty seat, no
+    /// AI opponent and no join request awaiting approval. Clients hide their
+    /// own lobbies by `creator`.
+    pub async fn open_lobbies(&self, limit: Option<usize>) -> Vec<GameSummary> {
+        let mut games = self
+            .collect_games(|game| {
+                game.status == GameStatus::Lobby
+                    && (game.white.is_none() || game.black.is_none())
+                    && ![PlayerColor::White, PlayerColor::Black]
+                        .into_iter()
+                        .any(|color| game.is_ai(color))
+                    && game.pending_joiner.is_none()
+            })
+            .await;
+        games.sort_by_key(|game| (game.created_at, game.game_id));
+        games.truncate(limit.unwrap_or(DEFAULT_GAMES_PAGE));
+        games
+    }
+
+    /// Engine-vs-engine games created by `SeedTrainingGames`.
+    pub async fn training_games(&self) -> Vec<GameSummary> {
+        self.scan_games(|game| game.training).await
+    }
+
+    /// Games where `chain_id` occupies the seat of the given color.
+    pub async fn games_as(&self, chain_id: ChainId, color: PlayerColor) -> Vec<GameSummary> {
+        self.collect_games(|game| game.seat(color) == Some(chain_id))
+            .await
+    }
 
-    /// Top leaderboard entries sorted by rating desc.
-    pub async fn top_players(&self, limit: Option<usize>) -> Vec<PlayerStats> {
+    /// Every game `chain` holds a seat in, finished ones included, most
+    /// recently updated first. Covers the games stored here and the mirrors
+    /// of games hosted on other chains; at most `limit` games
+    /// (`DEFAULT_GAMES_PAGE` by default) are returned.
+    pub async fn player_games(&self, chain: ChainId, limit: Option<usize>) -> Vec<GameSummary> {
+        let mut games = self
+            .collect_games(|game| game.color_of(chain).is_some())
+            .await;
+        if let Ok(keys) = self.remote_games.indices().await {
+            for key in keys {
+                if let Ok(Some(game)) = self.remote_games.get(&key).await {
+                    if game.color_of(chain).is_some() {
+                        games.push(game.to_summary());
+                    }
+                }
+            }
+        }
+        games.sort_by_key(|game| Reverse(game.updated_at));
+        games.truncate(limit.unwrap_or(DEFAULT_GAMES_PAGE));
+        games
+    }
+
+    /// Result of a game in standard notation; unknown games report "*".
+    pub async fn result_string(&self, game_id: u64) -> String {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => game.result_token().to_string(),
+            _ => "*".to_string(),
+        }
+    }
+
+    /// Pieces `chain_id` has captured in its games among the
+    /// `MAX_CAREER_SCAN_GAMES` most recently created on this chain. Only those
+    /// ids are read, so the cost stays bounded however many games are stored.
+    pub async fn career_captures(&self, chain_id: ChainId) -> CaptureCounts {
+        let mut counts = CaptureCounts::default();
+        let newest = *self.next_game_id.get();
+        for id in (1..newest).rev().take(MAX_CAREER_SCAN_GAMES) {
+            let Ok(Some(game)) = self.active_games.get(&id).await else {
+                continue;
+            };
+            let Some(color) = game.color_of(chain_id) else {
+                continue;
+            };
+            let history = self
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await;
+            for record in history.iter().filter(|record| record.played_by == color) {
+                if let Some(piece) = record.captured {
+                    counts.add(piece);
+                }
+            }
+        }
+        counts
+    }
+
+    /// Elo expected score of `a` against `b` at their current ratings.
+    pub async fn expected_score(&self, a: ChainId, b: ChainId) -> f32 {
+        expected_score(self.rating_of(a).await, self.rating_of(b).await)
+    }
+
+    /// Defaults `chain` has set for the games it creates.
+    pub async fn preferences(&self, chain: ChainId) -> Preferences {
+        self.player_preferences
+            .get(&chain)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    /// Finished games between `a` and `b`, from `a`'s point of view.
+    pub async fn head_to_head(&self, a: ChainId, b: ChainId) -> HeadToHead {
+        let key = if a <= b { (a, b) } else { (b, a) };
+        let record = self
+            .head_to_head_records
+            .get(&key)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if key.0 == a {
+            record
+        } else {
+            record.flipped()
+        }
+    }
+
+    /// How many of `chain_id`'s games are in the lobby, active or finished.
+    pub async fn my_counts(&self, chain_id: ChainId) -> StatusCounts {
+        let mut counts = StatusCounts::default();
+        for game in self
+            .collect_games(|game| game.color_of(chain_id).is_some())
+            .await
+        {
+            match game.status {
+                GameStatus::Lobby => counts.lobby += 1,
+                GameStatus::Active => counts.active += 1,
+                GameStatus::Finished => counts.finished += 1,
+            }
+        }
+        counts
+    }
+
+    /// Performance rating over the player's last `last_n` (default 10) finished
+    /// games against other chains: the mean opponent rating plus
+    /// 400 × (wins − losses) / games. Opponents are rated as they stand now.
+    /// Players with no such games get their stored rating.
+    pub async fn performance_rating(&self, chain_id: ChainId, last_n: Option<usize>) -> i32 {
+        let mut games = self
+            .collect_games(|game| {
+                game.status == GameStatus::Finished
+                    && game.white.is_some()
+                    && game.black.is_some()
+                    && game.color_of(chain_id).is_some()
+            })
+            .await;
+        games.sort_by_key(|game| Reverse(game.updated_at));
+        games.truncate(last_n.unwrap_or(10));
+
+        let own_rating = self.rating_of(chain_id).await;
+        if games.is_empty() {
+            return own_rating;
+        }
+        let mut opponent_total = 0i64;
+        let mut net_wins = 0i64;
+        for game in &games {
+            let Some(color) = game.color_of(chain_id) else {
+                continue;
+            };
+            if let Some(opponent) = game.seat(color.other()) {
+                opponent_total += i64::from(self.rating_of(opponent).await);
+            }
+            match game.winner {
+                Some(winner) if winner == color => net_wins += 1,
+                Some(_) => net_wins -= 1,
+                None => {}
+            }
+        }
+        let count = games.len() as i64;
+        ((opponent_total + 400 * net_wins) / count) as i32
+    }
+
+    /// Active games of `chain_id` needing attention after a reconnect: games
+    /// where it is the player's turn first, then most recently updated.
+    pub async fn resume_games(&self, chain_id: ChainId) -> Vec<GameSummary> {
+        let mut games = self
+            .collect_games(|game| {
+                game.status == GameStatus::Active && game.color_of(chain_id).is_some()
+            })
+            .await;
+        games.sort_by_key(|game| {
+            let my_turn = game.seat(game.turn) == Some(chain_id);
+            (Reverse(my_turn), Reverse(game.updated_at))
+        });
+        games
+    }
+
+    /// Algorithm, depth and feature flags of the deployed engine.
+    pub async fn engine_info(&self) -> EngineInfo {
+        engine_info()
+    }
+
+    /// Board, turn, check flag, legal moves and status in a single call.
+    pub async fn play_state(&self, game_id: u64) -> Option<PlayState> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        let legal_moves = if game.status == GameStatus::Active {
+            legal_moves(&game.board_fen)
+        } else {
+            Vec::new()
+        };
+        Some(PlayState {
+            in_check: in_check(&game.board_fen),
+            legal_moves,
+            turn: game.turn,
+            status: game.status,
+            board_fen: game.board_fen,
+        })
+    }
+
+    /// Legal moves in UCI for the side to move in an active game, optionally
+    /// only those of the piece standing on the `from` square (e.g. "e2").
+    /// Empty for unknown, unstarted and finished games.
+    pub async fn legal_moves(&self, game_id: u64, from: Option<String>) -> Vec<String> {
+        let Ok(Some(game)) = self.active_games.get(&game_id).await else {
+            return Vec::new();
+        };
+        if game.status != GameStatus::Active {
+            return Vec::new();
+        }
+        let from = from.map(|square| square.trim().to_ascii_lowercase());
+        legal_moves(&game.board_fen)
+            .into_iter()
+            .filter(|uci| {
+                from.as_ref()
+                    .is_none_or(|square| uci.get(..2) == Some(square.as_str()))
+            })
+            .collect()
+    }
+
+    /// The summary a move would produce, without persisting anything.
+    ///
+    /// Returns `None` for unknown or inactive games and for illegal moves.
+    pub async fn preview_move(
+        &self,
+        game_id: u64,
+        uci: String,
+        promotion: Option<String>,
+    ) -> Option<GameSummary> {
+        let mut game = self.active_games.get(&game_id).await.ok()??;
+        if game.status != GameStatus::Active {
+            return None;
+        }
+        let outcome = apply_uci_move(&game.board_fen, &uci, promotion.as_deref()).ok()?;
+        let at = game.updated_at;
+        if let Some(result) = game.record_move(outcome, at, None) {
+            game.finish(result, at);
+        }
+        Some(game.to_summary())
+    }
+
+    /// The game's board as a Unicode diagram for terminal clients.
+    pub async fn board_unicode(&self, game_id: u64) -> async_graphql::Result<String> {
+        let game = self
+            .active_games
+            .get(&game_id)
+            .await?
+            .ok_or_else(|| ChainChessError::GameNotFound(game_id).to_string())?;
+        board_unicode(&game.board_fen).ok_or_else(|| "stored position is not a valid FEN".into())
+    }
+
+    /// Compact code encoding the game's initial position and moves, which
+    /// `ImportShareCode` turns back into a game.
+    pub async fn share_code(&self, game_id: u64) -> async_graphql::Result<String> {
+        let game = self
+            .active_games
+            .get(&game_id)
+            .await?
+            .ok_or_else(|| ChainChessError::GameNotFound(game_id).to_string())?;
+        encode_share_code(
+            &game.initial_fen,
+            self.full_history(game.game_id, game.archived_moves, &game.moves)
+                .await
+                .iter()
+                .map(|record| record.uci.as_str()),
+        )
+        .ok_or_else(|| "stored moves could not be encoded".into())
+    }
+
+    /// The game in PGN, with `*` as the result while it is still going on;
+    /// `None` for unknown ids.
+    pub async fn pgn(&self, id: u64) -> Option<String> {
+        let game = self.active_games.get(&id).await.ok()??;
+        let player = |chain: Option<ChainId>, ai: bool| match chain {
+            Some(chain) => chain.to_string(),
+            None if ai => "ChainChess AI".to_string(),
+            None => "?".to_string(),
+        };
+        let tags = [
+            (
+                "Event",
+                game.metadata
+                    .clone()
+                    .unwrap_or_else(|| format!("ChainChess game {id}")),
+            ),
+            ("Site", "Linera".to_string()),
+            ("Date", pgn_date(game.created_at.micros())),
+            ("Round", "-".to_string()),
+            ("White", player(game.white, game.ai_white)),
+            ("Black", player(game.black, game.ai_black)),
+            ("Result", game.result_token().to_string()),
+        ];
+        let history = self
+            .full_history(game.game_id, game.archived_moves, &game.moves)
+            .await;
+        Some(encode_pgn(
+            &tags,
+            &game.initial_fen,
+            history
+                .iter()
+                .map(|record| record.san.as_deref().unwrap_or(&record.uci)),
+            game.result_token(),
+        ))
+    }
+
+    /// Squares of the side to move's pieces that are attacked and
+    /// insufficiently defended; see `chess_engine::hanging_pieces`.
+    pub async fn hanging_pieces(&self, game_id: u64) -> Vec<String> {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => hanging_pieces(&game.board_fen),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Moves until the side to move can force mate in the given game, if it
+    /// can within `max_depth` moves; see `chess_engine::forced_mate`.
+    pub async fn forced_mate(&self, game_id: u64, max_depth: u8) -> Option<u8> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        forced_mate(&game.board_fen, max_depth)
+    }
+
+    /// Half-moves since the last capture or pawn move in the given game.
+    pub async fn halfmove_clock(&self, game_id: u64) -> u32 {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => game.halfmove_clock,
+            _ => 0,
+        }
+    }
+
+    /// Moves by the side to move left before the fifty-move rule applies.
+    pub async fn moves_to_fifty(&self, game_id: u64) -> u32 {
+        let clock = match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => game.halfmove_clock,
+            _ => 0,
+        };
+        FIFTY_MOVE_PLIES.saturating_sub(clock).div_ceil(2)
+    }
+
+    /// Line of best play from the game's current position, up to `depth`
+    /// plies; see `chess_engine::principal_variation`.
+    pub async fn principal_variation(&self, game_id: u64, depth: u8) -> Vec<String> {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => principal_variation(&game.board_fen, depth),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Coach-mode warning for playing `uci` in the given game, or `None` if
+    /// the move looks sound; see `chess_engine::blunder_warning`.
+    pub async fn blunder_check(&self, game_id: u64, uci: String) -> Option<String> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        blunder_warning(&game.board_fen, &uci)
+    }
+
+    /// Why `fen` is not a legal position, or `None` if it is.
+    pub async fn validate_fen(&self, fen: String) -> Option<String> {
+        validate_fen(&fen).err().map(|err| err.to_string())
+    }
+
+    /// Why `uci` cannot be played in `fen`, for teaching and debugging.
+    pub async fn explain_illegal(&self, fen: String, uci: String) -> String {
+        explain_illegal(&fen, &uci)
+    }
+
+    /// Board FEN of a game after its first `ply` half-moves.
+    pub async fn board_at_ply(&self, game_id: u64, ply: usize) -> Option<String> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        game.board_at_ply(
+            &self
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await,
+            ply,
+        )
+    }
+
+    /// Replays a stored game and checks every move, the final board, the
+    /// turn and any on-board result against what is stored.
+    pub async fn verify_game(&self, game_id: u64) -> Option<GameVerification> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        let history = self
+            .full_history(game.game_id, game.archived_moves, &game.moves)
+            .await;
+        let (plies_checked, issue) = game.audit(&history);
+        Some(GameVerification {
+            valid: issue.is_none(),
+            plies_checked,
+            issue,
+        })
+    }
+
+    /// Whether a stored game replays to its stored board; `verify_game`
+    /// explains a failure.
+    pub async fn is_game_consistent(&self, game_id: u64) -> bool {
+        let Ok(Some(game)) = self.active_games.get(&game_id).await else {
+            return false;
+        };
+        let history = self
+            .full_history(game.game_id, game.archived_moves, &game.moves)
+            .await;
+        game.verify(&history).is_ok()
+    }
+
+    /// SAN of the game's latest move, or its UCI when no SAN was recorded.
+    pub async fn last_move_san(&self, game_id: u64) -> Option<String> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        let last = game.moves.last()?;
+        Some(last.san.clone().unwrap_or_else(|| last.uci.clone()))
+    }
+
+    /// Every move of a game, including those archived out of the game record.
+    pub async fn move_history(&self, game_id: u64) -> Vec<MoveRecord> {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => {
+                self.full_history(game.game_id, game.archived_moves, &game.moves)
+                    .await
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Changes to a game since the client's `since_version`, or `None` when
+    /// the client is up to date or the game is unknown.
+    pub async fn game_diff(&self, game_id: u64, since_version: u64) -> Option<GameDiff> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        if game.version <= since_version {
+            return None;
+        }
+        let history = self
+            .full_history(game.game_id, game.archived_moves, &game.moves)
+            .await;
+        // Moves are only ever added or taken back at the end, so the new
+        // ones form the tail of the history.
+        let known = history
+            .iter()
+            .rposition(|record| record.version <= since_version)
+            .map_or(0, |index| index + 1);
+        Some(GameDiff {
+            version: game.version,
+            status: game.status,
+            turn: game.turn,
+            winner: game.winner,
+            board_fen: game.board_fen,
+            move_count: history.len() as u32,
+            new_moves: history[known..].to_vec(),
+        })
+    }
+
+    /// The AI's static evaluation of a game's current position, in
+    /// centipawns from White's point of view.
+    pub async fn evaluate(&self, game_id: u64) -> Option<i32> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        evaluate_fen(&game.board_fen)
+    }
+
+    /// Static evaluation after each ply, replayed from the game's initial
+    /// position; capped at `MAX_EVAL_HISTORY_PLIES` plies.
+    pub async fn eval_history(&self, game_id: u64) -> Vec<i32> {
+        match self.active_games.get(&game_id).await {
+            Ok(Some(game)) => eval_history(
+                &game.initial_fen,
+                self.full_history(game.game_id, game.archived_moves, &game.moves)
+                    .await
+                    .iter()
+                    .map(|record| record.uci.as_str()),
+            ),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Promotion choices for the pawn move `uci` (e.g. "e7e8") in the given game.
+    pub async fn promotion_options(&self, game_id: u64, uci: String) -> Option<PromotionOptions> {
+        let game = self.active_games.get(&game_id).await.ok()??;
+        promotion_options(&game.board_fen, &uci.to_lowercase())
+    }
+
+    /// Opening explorer over finished games: every continuation of up to
+    /// `depth` moves (capped at `MAX_MOVE_TREE_DEPTH`) played after
+    /// `prefix_uci`, shorter lines first and then most played. Each call
+    /// scans every stored game.
+    pub async fn move_tree(&self, prefix_uci: Vec<String>, depth: u8) -> Vec<MoveStat> {
+        let prefix: Vec<String> = prefix_uci
+            .iter()
+            .map(|uci| uci.trim().to_ascii_lowercase())
+            .collect();
+        let depth = usize::from(depth.clamp(1, MAX_MOVE_TREE_DEPTH));
+        let mut tree: BTreeMap<Vec<String>, MoveStat> = BTreeMap::new();
+        for game in self
+            .collect_games(|game| game.status == GameStatus::Finished)
+            .await
+        {
+            let history = self
+                .full_history(game.game_id, game.archived_moves, &game.moves)
+                .await;
+            if history.len() <= prefix.len()
+                || !history
+                    .iter()
+                    .zip(&prefix)
+                    .all(|(record, uci)| record.uci == *uci)
+            {
+                continue;
+            }
+            let continuation: Vec<String> = history[prefix.len()..]
+                .iter()
+                .take(depth)
+                .map(|record| record.uci.clone())
+                .collect();
+            for len in 1..=continuation.len() {
+                let line = continuation[..len].to_vec();
+                let stat = tree.entry(line.clone()).or_insert_with(|| MoveStat {
+                    line,
+                    games: 0,
+                    white_wins: 0,
+                    black_wins: 0,
+                    draws: 0,
+                    white_win_rate: 0.0,
+                });
+                stat.games += 1;
+                match game.winner {
+                    Some(PlayerColor::White) => stat.white_wins += 1,
+                    Some(PlayerColor::Black) => stat.black_wins += 1,
+                    None => stat.draws += 1,
+                }
+            }
+        }
+        let mut stats: Vec<MoveStat> = tree
+            .into_values()
+            .map(|mut stat| {
+                stat.white_win_rate = f64::from(stat.white_wins) / f64::from(stat.games);
+                stat
+            })
+            .collect();
+        stats.sort_by_key(|stat| (stat.line.len(), Reverse(stat.games)));
+        stats
+    }
+
+    /// Finished draws by type, taken from each game's recorded termination.
+    pub async fn draws_by_reason(&self) -> DrawBreakdown {
+        let mut breakdown = DrawBreakdown::default();
+        for game in self
+            .collect_games(|game| game.status == GameStatus::Finished && game.winner.is_none())
+            .await
+        {
+            match game.termination {
+                Some(TerminationReason::Stalemate) => breakdown.stalemate += 1,
+                Some(TerminationReason::Threefold) => breakdown.repetition += 1,
+                Some(TerminationReason::FiftyMove) => breakdown.fifty_move += 1,
+                Some(TerminationReason::InsufficientMaterial) => {
+                    breakdown.insufficient_material += 1
+                }
+                Some(TerminationReason::Agreement) => breakdown.agreement += 1,
+                Some(_) => breakdown.other += 1,
+                None => {}
+            }
+        }
+        breakdown
+    }
+
+    /// Longest finished game and fastest checkmate win on this chain.
+    pub async fn records(&self) -> ChainRecords {
+        let mut records = ChainRecords::default();
+        for game in self
+            .collect_games(|game| game.status == GameStatus::Finished)
+            .await
+        {
+            let record = GameRecord {
+                game_id: game.game_id,
+                plies: game.archived_moves + game.moves.len() as u32,
+                winner: game.winner,
+            };
+            if records
+                .longest_game
+                .as_ref()
+                .is_none_or(|longest| record.plies > longest.plies)
+            {
+                records.longest_game = Some(record.clone());
+            }
+            if game.winner.is_some()
+                && is_checkmate(&game.board_fen)
+                && records
+                    .fastest_win
+                    .as_ref()
+                    .is_none_or(|fastest| record.plies < fastest.plies)
+            {
+                records.fastest_win = Some(record);
+            }
+        }
+        records
+    }
+
+    /// Top leaderboard entries sorted by rating desc, `limit` (10 by default)
+    /// at a time starting `offset` places down. With `decay` set the order
+    /// uses each player's `decayed_rating` instead of the raw rating.
+    pub async fn top_players(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<usize>,
+        decay: Option<bool>,
+        offset: Option<usize>,
+    ) -> Vec<PlayerStats> {
         let mut players = Vec::new();
         if let Ok(indices) = self.leaderboard.indices().await {
             for id in indices {
@@ -84,8 +1072,16 @@ impl ChainChessState {
                 }
             }
         }
-        players.sort_by_key(|p| Reverse(p.rating));
-        players.truncate(limit.unwrap_or(10));
+        match ctx.data_opt::<Timestamp>() {
+            Some(now) if decay.unwrap_or(false) => {
+                players.sort_by_key(|p| Reverse(p.rating_at(*now, &RatingDecay::DEFAULT)))
+            }
+            _ => players.sort_by_key(|p| Reverse(p.rating)),
+        }
         players
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(10))
+            .collect()
     }
 }
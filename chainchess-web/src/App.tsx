@@ -683,7 +683,7 @@ function ChainChessDashboard() {
                       {selectedGame.status}
                     </span>
                   )}
-                  {selectedGame?.aiBlack && (
+                  {(selectedGame?.aiBlack || selectedGame?.aiWhite) && (
                     <span className="badge ghost">
                       <Bot size={12} /> AI ally
                     </span>
@@ -830,7 +830,7 @@ function ChainChessDashboard() {
                       <span className="game-id">#{game.gameId}</span>
                     </div>
                     <p className="players">
-                      {shorten(game.white)} vs{' '}
+                      {game.white ? shorten(game.white) : game.aiWhite ? 'AI' : '—'} vs{' '}
                       {game.black ? shorten(game.black) : game.aiBlack ? 'AI' : '—'}
                     </p>
                     <p className="muted tiny">Updated {formatTimestamp(game.updatedAt)}</p>
@@ -1446,8 +1446,9 @@ function formatTimestamp(value?: number | string | null) {
 
 function gameTitle(game: GameSummary) {
   if (game.metadata) return game.metadata;
-  if (game.black) return `${shorten(game.white)} vs ${shorten(game.black)}`;
-  if (game.aiBlack) return `${shorten(game.white)} vs AI`;
+  if (game.white && game.black) return `${shorten(game.white)} vs ${shorten(game.black)}`;
+  if (game.white && game.aiBlack) return `${shorten(game.white)} vs AI`;
+  if (game.black && game.aiWhite) return `AI vs ${shorten(game.black)}`;
   return `Game #${game.gameId}`;
 }
 
@@ -16,6 +16,7 @@ const STATE_QUERY = gql`
       white
       black
       aiBlack
+      aiWhite
       boardFen
       metadata
       moves {
@@ -27,6 +28,7 @@ const STATE_QUERY = gql`
       status
       turn
       winner
+      termination
       createdAt
       updatedAt
     }
@@ -37,6 +39,8 @@ const STATE_QUERY = gql`
       draws
       gamesPlayed
       rating
+      currentStreak
+      bestWinStreak
     }
   }
 `;
@@ -54,6 +58,7 @@ const CREATE_GAME_MUTATION = gql`
         white
         black
         aiBlack
+        aiWhite
       }
     }
   }
@@ -92,6 +97,13 @@ const SUBMIT_MOVE_MUTATION = gql`
           playedAt
         }
         winner
+        termination
+      }
+      aiMove {
+        uci
+        san
+        playedBy
+        playedAt
       }
     }
   }
@@ -106,6 +118,7 @@ const RESIGN_MUTATION = gql`
         gameId
         status
         winner
+        termination
         turn
       }
     }
@@ -1,5 +1,16 @@
 export type PlayerColor = 'White' | 'Black';
 
+export type TerminationReason =
+  | 'Checkmate'
+  | 'Stalemate'
+  | 'Resignation'
+  | 'Agreement'
+  | 'Timeout'
+  | 'Abandoned'
+  | 'FiftyMove'
+  | 'Threefold'
+  | 'InsufficientMaterial';
+
 export interface MoveRecord {
   uci: string;
   san?: string | null;
@@ -9,14 +20,16 @@ export interface MoveRecord {
 
 export interface GameSummary {
   gameId: number;
-  white: string;
+  white?: string | null;
   black?: string | null;
   aiBlack: boolean;
+  aiWhite: boolean;
   boardFen: string;
   moves: MoveRecord[];
   turn: PlayerColor;
   status: 'Lobby' | 'Active' | 'Finished';
   winner?: PlayerColor | null;
+  termination?: TerminationReason | null;
   createdAt: number | string;
   updatedAt: number | string;
   metadata?: string | null;
@@ -29,6 +42,8 @@ export interface PlayerStats {
   draws: number;
   gamesPlayed: number;
   rating: number;
+  currentStreak: number;
+  bestWinStreak: number;
 }
 
 export interface ChainStateResponse {
@@ -40,6 +55,7 @@ export interface ChainChessResponse {
   success: boolean;
   message: string;
   game?: GameSummary | null;
+  aiMove?: MoveRecord | null;
 }
 
 export interface ChainConfig {
@@ -59,7 +59,7 @@ echo "📦 Building and publishing contract (linera project)..."
 MAX_RETRIES=2
 APP_ID=""
 for i in $(seq 1 $MAX_RETRIES); do
-  if OUTPUT=$(linera project publish-and-create "$APP_PATH" 2>&1); then
+  if OUTPUT=$(linera project publish-and-create "$APP_PATH" --json-parameters '{"max_open_games": 64}' 2>&1); then
     APP_ID=$(echo "$OUTPUT" | grep -oP 'linera_app::[a-f0-9]+' | head -1 || echo "$OUTPUT")
     if [[ "$APP_ID" =~ linera_app:: ]]; then
       break